@@ -6,15 +6,19 @@ use std::hash::Hash;
 use std::rc::Rc;
 
 use lox_lexer::Token;
+use serde::Serialize;
 
+use crate::is_truthy;
 use crate::push_env;
+use crate::ConversionError;
 use crate::Env;
 use crate::EnvErrorKind;
 use crate::Result;
 use crate::Span;
 use crate::TokenType;
+use crate::Truthiness;
 
-#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Serialize)]
 pub enum UnaryOp {
     Minus,
     Not,
@@ -30,7 +34,7 @@ impl Display for UnaryOp {
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Serialize)]
 pub enum BinaryOp {
     Add, // +
     Sub, // -
@@ -87,10 +91,15 @@ impl From<TokenType> for BinaryOp {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Identifier {
     pub token: Token,
+    #[serde(skip)]
     pub rid: usize,
+    /// Position of the parameter this name refers to, set by the resolver. Parameters
+    /// live in a vector of their call environment instead of its map of names.
+    #[serde(skip)]
+    pub slot: Option<u32>,
 }
 
 impl Display for Identifier {
@@ -101,7 +110,11 @@ impl Display for Identifier {
 
 impl From<Token> for Identifier {
     fn from(token: Token) -> Self {
-        Self { token, rid: 0 }
+        Self {
+            token,
+            rid: 0,
+            slot: None,
+        }
     }
 }
 
@@ -113,6 +126,8 @@ impl PartialEq for Identifier {
 
 impl Eq for Identifier {}
 
+// Equality and hashing include the span, so each use site of a name is a distinct key.
+// Lookups that should only care about the name go through `EnvInner::lookup` instead.
 impl Hash for Identifier {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.token.span.line.hash(state);
@@ -121,7 +136,7 @@ impl Hash for Identifier {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Argument {
     pub value: Expr,
 }
@@ -140,7 +155,7 @@ impl From<Argument> for Expr {
 
 pub type Arguments = Vec<Argument>;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum Expr {
     Nil,
     Int(i64),
@@ -148,13 +163,18 @@ pub enum Expr {
     Boolean(bool),
     Ident(Identifier),
     String(String),
-    Unary(UnaryOp, Box<Expr>),
-    Binary(BinaryOp, Box<Expr>, Box<Expr>),
-    Assign(Box<Expr>, Box<Expr>),
-    Logical(BinaryOp, Box<Expr>, Box<Expr>),
-    Call(Box<Expr>, Arguments),
-    Lambda(Vec<Identifier>, Vec<Stmt>),
+    /// `${expr}` inside a string literal: the value of `expr` as a string.
+    Interpolation(Box<Expr>),
+    Unary(UnaryOp, Box<Expr>, Span),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>, Span),
+    Assign(Box<Expr>, Box<Expr>, Span),
+    Logical(BinaryOp, Box<Expr>, Box<Expr>, Span),
+    Call(Box<Expr>, Arguments, Span),
+    /// Parameters, their annotations as in `FunctionDecl`, and the body.
+    Lambda(Vec<Identifier>, Annotations, Vec<Stmt>),
     Get(Box<Expr>, Identifier),
+    /// `object?.property`, which is `nil` when `object` is.
+    GetSafe(Box<Expr>, Identifier),
     Set(Box<Expr>, Identifier, Box<Expr>),
     This(Identifier),
     Super(Identifier, Identifier),
@@ -162,40 +182,67 @@ pub enum Expr {
 
 impl Eq for Expr {}
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct VariableDecl {
     pub name: Identifier,
     pub definition: Option<Expr>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// The type written after each parameter name, as in `fun f(a: int)`. Empty when no
+/// parameter has one, which keeps unannotated functions free of the extra allocation.
+pub type Annotations = Vec<Option<Box<str>>>;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct FunctionDecl {
     pub name: Identifier,
     pub params: Vec<Identifier>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub annotations: Annotations,
     pub body: Vec<Stmt>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Conditional {
     pub cond: Expr,
     pub if_branch: Box<Stmt>,
     pub else_branch: Option<Box<Stmt>>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Loop {
     pub cond: Expr,
     pub body: Box<Stmt>,
+    /// The update clause of a `for`, run after every iteration including one cut short
+    /// by `continue`.
+    pub update: Option<Expr>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// `for (var name in iterable) body`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ForEach {
+    pub name: Identifier,
+    pub iterable: Expr,
+    pub body: Box<Stmt>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct ClassDecl {
     pub name: Identifier,
     pub super_class: Option<Expr>,
     pub methods: Vec<FunctionDecl>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// `import "file.lox";`, which runs the file in the global environment, or
+/// `import "file.lox" as m;`, which binds its top-level names to the module `m` instead.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Import {
+    pub path: String,
+    pub alias: Option<Identifier>,
+    /// The `import` keyword.
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum Stmt {
     Print(Expr),
     Expr(Expr),
@@ -203,16 +250,82 @@ pub enum Stmt {
     Block(Vec<Stmt>),
     Conditional(Conditional),
     Loop(Loop),
+    ForEach(ForEach),
     FunctionDecl(FunctionDecl),
-    Return(Expr),
-    Break,
+    /// The value, `Expr::Nil` for a bare `return;`, and the `return` keyword.
+    Return(Expr, Span),
+    Break(Span),
+    Continue(Span),
+    Import(Import),
     ClassDecl(ClassDecl),
 }
 
+impl Expr {
+    /// Where the expression starts, if it contains a token to tell.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Expr::Nil | Expr::Int(_) | Expr::Float(_) | Expr::Boolean(_) | Expr::String(_) => None,
+            Expr::Ident(id) | Expr::This(id) | Expr::Super(id, _) => Some(id.token.span),
+            Expr::Interpolation(e) => e.span(),
+            Expr::Unary(_, _, span) => Some(*span),
+            Expr::Binary(_, l, _, span) | Expr::Logical(_, l, _, span) => l.span().or(Some(*span)),
+            Expr::Call(callee, _, span) => callee.span().or(Some(*span)),
+            Expr::Assign(target, _, span) => target.span().or(Some(*span)),
+            Expr::Get(target, _) | Expr::GetSafe(target, _) | Expr::Set(target, _, _) => {
+                target.span()
+            }
+            Expr::Lambda(_, _, body) => body.iter().find_map(Stmt::span),
+        }
+    }
+}
+
+impl Stmt {
+    /// The keyword introducing the statement, or `expression` for an expression statement.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Stmt::Print(_) => "print",
+            Stmt::Expr(_) => "expression",
+            Stmt::VariableDecl(_) => "var",
+            Stmt::Block(_) => "block",
+            Stmt::Conditional(_) => "if",
+            Stmt::Loop(_) => "while",
+            Stmt::ForEach(_) => "for",
+            Stmt::FunctionDecl(_) => "fun",
+            Stmt::Return(..) => "return",
+            Stmt::Break(_) => "break",
+            Stmt::Continue(_) => "continue",
+            Stmt::Import(_) => "import",
+            Stmt::ClassDecl(_) => "class",
+        }
+    }
+
+    /// Where the statement starts, as far as its tokens tell: the keyword of `return`,
+    /// `break`, `continue` and `import`, otherwise the first name or operator inside it.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Stmt::Print(e) | Stmt::Expr(e) => e.span(),
+            Stmt::Return(_, span)
+            | Stmt::Break(span)
+            | Stmt::Continue(span)
+            | Stmt::Import(Import { span, .. }) => Some(*span),
+            Stmt::VariableDecl(VariableDecl { name, .. })
+            | Stmt::ForEach(ForEach { name, .. })
+            | Stmt::FunctionDecl(FunctionDecl { name, .. })
+            | Stmt::ClassDecl(ClassDecl { name, .. }) => Some(name.token.span),
+            Stmt::Block(stmts) => stmts.iter().find_map(Stmt::span),
+            Stmt::Conditional(Conditional { cond, .. }) | Stmt::Loop(Loop { cond, .. }) => {
+                cond.span()
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct FuncObject {
     pub name: Option<Identifier>,
     pub params: Rc<Vec<Identifier>>,
+    /// `None` when no parameter is annotated.
+    pub annotations: Option<Rc<[Option<Box<str>>]>>,
     pub body: Rc<Vec<Stmt>>,
     pub closure: Env,
     pub is_initializer: bool,
@@ -230,6 +343,7 @@ impl FuncObject {
         Self {
             name: Some(name),
             params: Rc::new(params),
+            annotations: None,
             body: Rc::new(body),
             closure,
             is_initializer,
@@ -241,12 +355,20 @@ impl FuncObject {
         Self {
             name: None,
             params: Rc::new(params),
+            annotations: None,
             body: Rc::new(body),
             closure,
             is_initializer: false,
         }
     }
 
+    /// Parameter annotations, checked on each call when the interpreter asks for it.
+    #[inline(always)]
+    pub fn with_annotations(mut self, annotations: Annotations) -> Self {
+        self.annotations = (!annotations.is_empty()).then(|| annotations.into());
+        self
+    }
+
     #[inline(always)]
     pub fn bind(f: FuncObject, instance: Rc<RefCell<ClassInstance>>) -> Result<Self> {
         let env = push_env(f.closure);
@@ -256,11 +378,31 @@ impl FuncObject {
         );
         Ok(Self { closure: env, ..f })
     }
+
+    /// Names visible through the closure's environment chain, innermost first.
+    pub fn captured_names(&self) -> Vec<String> {
+        let mut names = vec![];
+        let mut env = Some(Rc::clone(&self.closure));
+        while let Some(inner) = env {
+            let inner = inner.borrow();
+            let mut level: Vec<_> = inner
+                .variables()
+                .map(|(name, _)| name)
+                .filter(|name| !names.contains(*name))
+                .cloned()
+                .collect();
+            level.sort();
+            names.extend(level);
+            env = inner.enclosing.clone();
+        }
+        names
+    }
 }
 
+/// Functions are equal only to themselves: every closure or method binding is distinct.
 impl PartialEq for FuncObject {
     fn eq(&self, other: &Self) -> bool {
-        self.name == other.name && self.params == other.params && self.body == other.body
+        Rc::ptr_eq(&self.body, &other.body) && Rc::ptr_eq(&self.closure, &other.closure)
     }
 }
 
@@ -283,10 +425,80 @@ impl Display for FuncObject {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub arity: usize,
+}
+
+impl Display for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+/// A native implemented by the embedding application, see `Interpreter::register_native`.
+#[derive(Clone)]
+pub struct HostFunction {
+    pub name: String,
+    pub arity: usize,
+    pub func: Rc<dyn Fn(Vec<Object>) -> anyhow::Result<Object>>,
+}
+
+impl PartialEq for HostFunction {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.func, &other.func)
+    }
+}
+
+impl Debug for HostFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HostFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish()
+    }
+}
+
+impl Display for HostFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+/// The top-level names of a file imported with `import "file.lox" as name;`.
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub name: String,
+    pub env: Env,
+}
+
+impl PartialEq for Module {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.env, &other.env)
+    }
+}
+
+impl Display for Module {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<module {}>", self.name)
+    }
+}
+
+impl Module {
+    #[inline(always)]
+    pub fn get(&self, property: &str) -> Result<Object> {
+        match self.env.borrow().lookup(property) {
+            Some(o) => Ok(o.borrow().clone()),
+            None => Err(EnvErrorKind::UndefinedProperty(property.into())),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct ClassObject {
     pub name: Identifier,
-    pub super_class: Option<Box<ClassObject>>,
+    pub super_class: Option<Rc<ClassObject>>,
     pub methods: HashMap<String, FuncObject>,
 }
 
@@ -300,7 +512,7 @@ impl ClassObject {
     #[inline(always)]
     pub fn new(
         name: Identifier,
-        super_class: Option<Box<ClassObject>>,
+        super_class: Option<Rc<ClassObject>>,
         methods: Vec<(String, FuncObject)>,
     ) -> Self {
         Self {
@@ -373,6 +585,12 @@ impl ClassInstance {
     pub fn set(&mut self, property: String, value: Object) {
         self.fields.insert(property, value);
     }
+
+    /// Names of the fields set on the instance, in no particular order. Methods are not
+    /// included.
+    pub fn field_names(&self) -> impl Iterator<Item = &String> {
+        self.fields.keys()
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -383,29 +601,372 @@ pub enum Object {
     Boolean(bool),
     String(String),
     Function(FuncObject),
-    Class(ClassObject),
+    NativeFunction(NativeFunction),
+    HostFunction(HostFunction),
+    Class(Rc<ClassObject>),
     Instance(Rc<RefCell<ClassInstance>>),
+    /// The half-open integer range `start..end`.
+    Range {
+        start: i64,
+        end: i64,
+    },
+    Module(Module),
+}
+
+/// Formats a float the way `print` shows it. `-0.0` prints as `0`, and magnitudes from
+/// `1e21` up or below `1e-6` switch to exponent notation, as in JavaScript, instead of
+/// writing out every digit.
+fn format_float(f: f64) -> String {
+    if f == 0.0 {
+        "0".into()
+    } else if f.is_finite() && (f.abs() >= 1e21 || f.abs() < 1e-6) {
+        format!("{:e}", f)
+    } else {
+        f.to_string()
+    }
 }
 
 impl Display for Object {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Object::Nil => write!(f, "nil"),
-            Object::Int(i) => write!(f, "{}", *i),
-            Object::Float(fl) => write!(f, "{}", *fl),
-            Object::Boolean(b) => write!(f, "{}", *b),
-            Object::String(s) => write!(f, "\"{}\"", s),
-            Object::Function(fo) => write!(f, "{}", fo),
-            Object::Class(co) => write!(f, "{}", co),
-            Object::Instance(ci) => write!(f, "{}", ci.borrow()),
-        }
+        write!(f, "{}", self.render(true))
     }
 }
 
 impl Object {
+    /// Renders the object for output. Only a top-level string is affected by `quoted`;
+    /// values nested inside another value always render the way `Display` does.
+    pub fn render(&self, quoted: bool) -> String {
+        match self {
+            Object::Nil => "nil".into(),
+            Object::Int(i) => i.to_string(),
+            Object::Float(fl) => format_float(*fl),
+            Object::Boolean(b) => b.to_string(),
+            Object::String(s) if quoted => format!("\"{}\"", s),
+            Object::String(s) => s.clone(),
+            Object::Function(fo) => fo.to_string(),
+            Object::NativeFunction(nf) => nf.to_string(),
+            Object::HostFunction(hf) => hf.to_string(),
+            Object::Class(co) => co.to_string(),
+            Object::Instance(ci) => ci.borrow().to_string(),
+            Object::Range { start, end } => format!("{}..{}", start, end),
+            Object::Module(m) => m.to_string(),
+        }
+    }
+
     #[inline(always)]
     pub fn is_truth(&self) -> bool {
+        is_truthy(self, Truthiness::Lox)
+    }
+
+    /// Like `is_truth`, but `0`, `0.0` and `""` are also falsey.
+    #[inline(always)]
+    pub fn is_c_like_truth(&self) -> bool {
+        is_truthy(self, Truthiness::CLike)
+    }
+
+    #[inline(always)]
+    pub fn is_callable(&self) -> bool {
+        matches!(
+            self,
+            Object::Function(_)
+                | Object::NativeFunction(_)
+                | Object::HostFunction(_)
+                | Object::Class(_)
+        )
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Nil => "nil",
+            Object::Int(_) | Object::Float(_) => "number",
+            Object::Boolean(_) => "boolean",
+            Object::String(_) => "string",
+            Object::Function(_) | Object::NativeFunction(_) | Object::HostFunction(_) => "function",
+            Object::Class(_) => "class",
+            Object::Instance(_) => "instance",
+            Object::Range { .. } => "range",
+            Object::Module(_) => "module",
+        }
+    }
+
+    /// Lox `==`: numbers compare by value across `Int` and `Float`, classes and instances
+    /// by identity.
+    pub fn equals(&self, other: &Object) -> bool {
         use Object::*;
-        !matches!(self, Nil | Boolean(false))
+        match (self, other) {
+            (Int(a), Float(b)) | (Float(b), Int(a)) => *a as f64 == *b,
+            (Class(a), Class(b)) => Rc::ptr_eq(a, b),
+            (Instance(a), Instance(b)) => Rc::ptr_eq(a, b),
+            (a, b) => a == b,
+        }
+    }
+
+    /// Hashable form of a value-typed object, `None` for functions, classes and instances.
+    pub fn try_as_key(&self) -> Option<MapKey> {
+        match self {
+            Object::Nil => Some(MapKey::Nil),
+            Object::Int(i) => Some(MapKey::Int(*i)),
+            // Keep keys consistent with `equals`, where `1 == 1.0`.
+            Object::Float(f) if f.fract() == 0.0 && (*f as i64) as f64 == *f => {
+                Some(MapKey::Int(*f as i64))
+            }
+            Object::Float(f) => Some(MapKey::Float(f.to_bits())),
+            Object::Boolean(b) => Some(MapKey::Boolean(*b)),
+            Object::String(s) => Some(MapKey::String(s.clone())),
+            Object::Function(_)
+            | Object::NativeFunction(_)
+            | Object::HostFunction(_)
+            | Object::Class(_)
+            | Object::Instance(_)
+            | Object::Range { .. }
+            | Object::Module(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    Nil,
+    Int(i64),
+    Float(u64),
+    Boolean(bool),
+    String(String),
+}
+
+impl Object {
+    pub fn nil() -> Self {
+        Object::Nil
+    }
+
+    pub fn int(i: i64) -> Self {
+        Object::Int(i)
+    }
+
+    pub fn float(f: f64) -> Self {
+        Object::Float(f)
+    }
+
+    pub fn string(s: impl Into<String>) -> Self {
+        Object::String(s.into())
+    }
+
+    #[inline(always)]
+    fn mismatch(&self, expected: &'static str) -> ConversionError {
+        ConversionError {
+            expected,
+            found: self.type_name(),
+        }
+    }
+
+    /// Accepts an `Int`, or a finite `Float` without a fractional part that fits in an
+    /// `i64`. `i64::MAX as f64` rounds up to 2^63, so the upper bound is exclusive.
+    pub fn try_into_i64(&self) -> std::result::Result<i64, ConversionError> {
+        match self {
+            Object::Int(i) => Ok(*i),
+            Object::Float(f)
+                if f.fract() == 0.0 && (i64::MIN as f64..i64::MAX as f64).contains(f) =>
+            {
+                Ok(*f as i64)
+            }
+            o => Err(o.mismatch("integer")),
+        }
+    }
+
+    pub fn try_into_f64(&self) -> std::result::Result<f64, ConversionError> {
+        match self {
+            Object::Int(i) => Ok(*i as f64),
+            Object::Float(f) => Ok(*f),
+            o => Err(o.mismatch("number")),
+        }
+    }
+
+    pub fn try_into_string(&self) -> std::result::Result<String, ConversionError> {
+        match self {
+            Object::String(s) => Ok(s.clone()),
+            o => Err(o.mismatch("string")),
+        }
+    }
+
+    pub fn try_into_bool(&self) -> std::result::Result<bool, ConversionError> {
+        match self {
+            Object::Boolean(b) => Ok(*b),
+            o => Err(o.mismatch("boolean")),
+        }
+    }
+}
+
+impl From<()> for Object {
+    fn from(_: ()) -> Self {
+        Object::Nil
+    }
+}
+
+impl From<i64> for Object {
+    fn from(i: i64) -> Self {
+        Object::Int(i)
+    }
+}
+
+impl From<f64> for Object {
+    fn from(f: f64) -> Self {
+        Object::Float(f)
+    }
+}
+
+impl From<bool> for Object {
+    fn from(b: bool) -> Self {
+        Object::Boolean(b)
+    }
+}
+
+impl From<String> for Object {
+    fn from(s: String) -> Self {
+        Object::String(s)
+    }
+}
+
+impl From<&str> for Object {
+    fn from(s: &str) -> Self {
+        Object::String(s.into())
+    }
+}
+
+impl From<MapKey> for Object {
+    fn from(key: MapKey) -> Self {
+        match key {
+            MapKey::Nil => Object::Nil,
+            MapKey::Int(i) => Object::Int(i),
+            MapKey::Float(bits) => Object::Float(f64::from_bits(bits)),
+            MapKey::Boolean(b) => Object::Boolean(b),
+            MapKey::String(s) => Object::String(s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::Hasher;
+
+    use super::*;
+
+    fn hash(o: &Object) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        o.try_as_key().expect("hashable object").hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn equal_values_hash_equal() {
+        assert_eq!(hash(&Object::Int(42)), hash(&Object::Int(42)));
+        assert_eq!(
+            hash(&Object::String("key".into())),
+            hash(&Object::String("key".into()))
+        );
+        assert_eq!(hash(&Object::Boolean(true)), hash(&Object::Boolean(true)));
+        assert_eq!(hash(&Object::Int(1)), hash(&Object::Float(1.0)));
+        assert_ne!(hash(&Object::Int(1)), hash(&Object::String("1".into())));
+    }
+
+    #[test]
+    fn key_round_trip() {
+        for o in [
+            Object::Nil,
+            Object::Int(-3),
+            Object::Float(2.5),
+            Object::Boolean(false),
+            Object::String("s".into()),
+        ] {
+            assert_eq!(Object::from(o.try_as_key().unwrap()), o);
+        }
+    }
+
+    #[test]
+    fn constructors() {
+        assert_eq!(Object::nil(), Object::from(()));
+        assert_eq!(Object::int(3), Object::from(3));
+        assert_eq!(Object::float(0.5), Object::from(0.5));
+        assert_eq!(Object::string("lox"), Object::from(String::from("lox")));
+        assert_eq!(Object::string("lox").try_into_string().unwrap(), "lox");
+    }
+
+    #[test]
+    fn render_quoting() {
+        let s = Object::String("a".into());
+        assert_eq!(s.render(true), "\"a\"");
+        assert_eq!(s.render(false), "a");
+        assert_eq!(s.to_string(), s.render(true));
+        assert_eq!(Object::Float(1.5).render(false), "1.5");
+        assert_eq!(Object::Nil.render(false), "nil");
+    }
+
+    #[test]
+    fn render_floats() {
+        for (f, rendered) in [
+            (-0.0, "0"),
+            (0.0001, "0.0001"),
+            (-0.001, "-0.001"),
+            (1e300, "1e300"),
+            (1.5e-7, "1.5e-7"),
+            (123456.5, "123456.5"),
+            (f64::INFINITY, "inf"),
+        ] {
+            assert_eq!(Object::Float(f).to_string(), rendered);
+        }
+    }
+
+    #[test]
+    fn reference_types_are_not_keys() {
+        let class = ClassObject::new(
+            Token::new_with_lexeme(TokenType::Ident, "A", Span::default()).into(),
+            None,
+            vec![],
+        );
+        assert_eq!(Object::Class(Rc::new(class)).try_as_key(), None);
+    }
+
+    #[test]
+    fn host_conversions() {
+        assert_eq!(Object::Int(7).try_into_i64(), Ok(7));
+        assert_eq!(Object::Float(7.0).try_into_i64(), Ok(7));
+        assert_eq!(Object::Int(2).try_into_f64(), Ok(2.0));
+        assert_eq!(Object::from("lox").try_into_string(), Ok("lox".into()));
+        assert_eq!(Object::from(true).try_into_bool(), Ok(true));
+        assert_eq!(Object::from(3), Object::Int(3));
+        assert_eq!(Object::from(0.5), Object::Float(0.5));
+        assert_eq!(Object::from(String::from("s")), Object::String("s".into()));
+        assert_eq!(Object::from(()), Object::Nil);
+    }
+
+    #[test]
+    fn host_conversion_errors() {
+        let e = Object::from("1").try_into_i64().unwrap_err();
+        assert_eq!(e.to_string(), "Expected integer but got string.");
+        assert!(Object::Float(1.5).try_into_i64().is_err());
+        assert!(Object::Nil.try_into_f64().is_err());
+        assert!(Object::Int(1).try_into_string().is_err());
+        assert!(Object::Int(1).try_into_bool().is_err());
+    }
+
+    #[test]
+    fn float_to_i64_edges() {
+        assert_eq!(Object::Float(i64::MIN as f64).try_into_i64(), Ok(i64::MIN));
+        assert_eq!(Object::Float(-0.0).try_into_i64(), Ok(0));
+        assert_eq!(
+            Object::Float(9007199254740992.0).try_into_i64(),
+            Ok(1 << 53)
+        );
+        for f in [
+            i64::MAX as f64,
+            -(i64::MIN as f64),
+            i64::MIN as f64 * 2.0,
+            1e300,
+            f64::NAN,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+        ] {
+            let e = Object::Float(f).try_into_i64().unwrap_err();
+            assert_eq!(e.to_string(), "Expected integer but got number.", "{}", f);
+        }
     }
 }
@@ -0,0 +1,82 @@
+use crate::Object;
+
+/// Which values count as false in conditions and logical operators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Truthiness {
+    /// Only `nil` and `false`, as in reference Lox.
+    #[default]
+    Lox,
+    /// Additionally `0`, `0.0` and `""`.
+    CLike,
+}
+
+pub fn is_truthy(o: &Object, truthiness: Truthiness) -> bool {
+    match (o, truthiness) {
+        (Object::Nil | Object::Boolean(false), _) => false,
+        (Object::Int(0), Truthiness::CLike) => false,
+        (Object::Float(f), Truthiness::CLike) if *f == 0.0 => false,
+        (Object::String(s), Truthiness::CLike) if s.is_empty() => false,
+        _ => true,
+    }
+}
+
+/// The operands of an arithmetic operator, brought to a common type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Numbers {
+    Ints(i64, i64),
+    Floats(f64, f64),
+}
+
+/// Two integers stay integers; if either operand is a float, both become floats. `None`
+/// unless both are numbers.
+pub fn promote(a: &Object, b: &Object) -> Option<Numbers> {
+    match (a, b) {
+        (Object::Int(a), Object::Int(b)) => Some(Numbers::Ints(*a, *b)),
+        (Object::Int(a), Object::Float(b)) => Some(Numbers::Floats(*a as f64, *b)),
+        (Object::Float(a), Object::Int(b)) => Some(Numbers::Floats(*a, *b as f64)),
+        (Object::Float(a), Object::Float(b)) => Some(Numbers::Floats(*a, *b)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truthiness_matrix() {
+        for (o, lox, c_like) in [
+            (Object::Nil, false, false),
+            (Object::Boolean(false), false, false),
+            (Object::Boolean(true), true, true),
+            (Object::Int(0), true, false),
+            (Object::Int(-1), true, true),
+            (Object::Float(0.0), true, false),
+            (Object::Float(-0.0), true, false),
+            (Object::Float(0.5), true, true),
+            (Object::String("".into()), true, false),
+            (Object::String("0".into()), true, true),
+            (Object::Range { start: 0, end: 0 }, true, true),
+        ] {
+            assert_eq!(is_truthy(&o, Truthiness::Lox), lox, "{:?}", o);
+            assert_eq!(is_truthy(&o, Truthiness::CLike), c_like, "{:?}", o);
+        }
+    }
+
+    #[test]
+    fn promotion_matrix() {
+        let (int, float) = (Object::Int(2), Object::Float(0.5));
+        assert_eq!(promote(&int, &int), Some(Numbers::Ints(2, 2)));
+        assert_eq!(promote(&int, &float), Some(Numbers::Floats(2.0, 0.5)));
+        assert_eq!(promote(&float, &int), Some(Numbers::Floats(0.5, 2.0)));
+        assert_eq!(promote(&float, &float), Some(Numbers::Floats(0.5, 0.5)));
+        for other in [
+            Object::Nil,
+            Object::Boolean(true),
+            Object::String("1".into()),
+        ] {
+            assert_eq!(promote(&int, &other), None);
+            assert_eq!(promote(&other, &float), None);
+        }
+    }
+}
@@ -10,6 +10,9 @@ use crate::Result;
 #[derive(Debug)]
 pub struct EnvInner {
     pub(crate) values: HashMap<String, Rc<RefCell<Object>>>,
+    /// Arguments of a call environment, in the order of `params`.
+    slots: Vec<Rc<RefCell<Object>>>,
+    params: Option<Rc<Vec<Identifier>>>,
     pub enclosing: Option<Rc<RefCell<EnvInner>>>,
 }
 
@@ -24,6 +27,8 @@ impl EnvInner {
     pub fn new() -> Self {
         Self {
             values: HashMap::new(),
+            slots: Vec::new(),
+            params: None,
             enclosing: None,
         }
     }
@@ -32,6 +37,25 @@ impl EnvInner {
     pub fn detach_env(enclosing: Rc<RefCell<EnvInner>>) -> Self {
         Self {
             values: HashMap::new(),
+            slots: Vec::new(),
+            params: None,
+            enclosing: Some(enclosing),
+        }
+    }
+
+    /// A call environment binding `args` to `params` by position.
+    pub fn with_params(
+        enclosing: Rc<RefCell<EnvInner>>,
+        params: Rc<Vec<Identifier>>,
+        args: Vec<Object>,
+    ) -> Self {
+        Self {
+            values: HashMap::new(),
+            slots: args
+                .into_iter()
+                .map(|arg| Rc::new(RefCell::new(arg)))
+                .collect(),
+            params: Some(params),
             enclosing: Some(enclosing),
         }
     }
@@ -42,10 +66,43 @@ impl EnvInner {
             .insert(id.token.lexeme, Rc::new(RefCell::new(o)));
     }
 
+    #[inline(always)]
+    pub fn contains(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+
+    /// The variables defined directly in this environment, including call parameters.
+    pub fn variables(&self) -> impl Iterator<Item = (&String, &Rc<RefCell<Object>>)> {
+        let params = self
+            .params
+            .iter()
+            .flat_map(|params| params.iter())
+            .map(|param| &param.token.lexeme);
+        self.values.iter().chain(params.zip(&self.slots))
+    }
+
+    /// Finds `name` in this environment or the nearest enclosing one that defines it.
+    pub fn lookup(&self, name: &str) -> Option<Rc<RefCell<Object>>> {
+        if let Some(o) = self.values.get(name) {
+            return Some(Rc::clone(o));
+        }
+        let param = self
+            .params
+            .as_ref()
+            .and_then(|params| params.iter().position(|p| p.token.lexeme == name));
+        match param {
+            Some(slot) => self.slots.get(slot).cloned(),
+            None => self.enclosing.as_ref()?.borrow().lookup(name),
+        }
+    }
+
     #[inline(always)]
     pub(crate) fn _get(env: &EnvInner, id: &Identifier, up: usize) -> Result<Rc<RefCell<Object>>> {
         match up {
-            0 => match env.values.get(&id.token.lexeme) {
+            0 => match id.slot.map_or_else(
+                || env.values.get(&id.token.lexeme),
+                |slot| env.slots.get(slot as usize),
+            ) {
                 Some(o) => Ok(Rc::clone(o)),
                 None => Err(EnvErrorKind::UndefinedVariable(id.clone())),
             },
@@ -0,0 +1,183 @@
+use crate::ast::*;
+
+/// Replaces arithmetic over number literals with its result, e.g. `1 + 2 * 3` with `7`.
+/// Anything that would fail or overflow at runtime, like `1 / 0`, is left as is so the
+/// evaluator still reports it.
+pub fn fold_constants(stmts: &mut [Stmt]) {
+    for stmt in stmts {
+        fold_stmt(stmt);
+    }
+}
+
+fn fold_stmt(stmt: &mut Stmt) {
+    match stmt {
+        Stmt::Print(e) | Stmt::Expr(e) | Stmt::Return(e, _) => fold_expr(e),
+        Stmt::VariableDecl(VariableDecl { definition, .. }) => {
+            if let Some(e) = definition {
+                fold_expr(e);
+            }
+        }
+        Stmt::Block(stmts) => fold_constants(stmts),
+        Stmt::Conditional(Conditional {
+            cond,
+            if_branch,
+            else_branch,
+        }) => {
+            fold_expr(cond);
+            fold_stmt(if_branch);
+            if let Some(else_branch) = else_branch {
+                fold_stmt(else_branch);
+            }
+        }
+        Stmt::Loop(Loop { cond, body, update }) => {
+            fold_expr(cond);
+            fold_stmt(body);
+            if let Some(update) = update {
+                fold_expr(update);
+            }
+        }
+        Stmt::ForEach(ForEach { iterable, body, .. }) => {
+            fold_expr(iterable);
+            fold_stmt(body);
+        }
+        Stmt::FunctionDecl(FunctionDecl { body, .. }) => fold_constants(body),
+        Stmt::ClassDecl(ClassDecl {
+            super_class,
+            methods,
+            ..
+        }) => {
+            if let Some(e) = super_class {
+                fold_expr(e);
+            }
+            for method in methods {
+                fold_constants(&mut method.body);
+            }
+        }
+        Stmt::Break(_) | Stmt::Continue(_) | Stmt::Import(_) => {}
+    }
+}
+
+fn fold_expr(expr: &mut Expr) {
+    match expr {
+        Expr::Unary(_, e, _) | Expr::Interpolation(e) | Expr::Get(e, _) | Expr::GetSafe(e, _) => {
+            fold_expr(e)
+        }
+        Expr::Binary(_, l, r, _) | Expr::Logical(_, l, r, _) | Expr::Assign(l, r, _) => {
+            fold_expr(l);
+            fold_expr(r);
+        }
+        Expr::Set(object, _, value) => {
+            fold_expr(object);
+            fold_expr(value);
+        }
+        Expr::Call(callee, args, _) => {
+            fold_expr(callee);
+            for arg in args {
+                fold_expr(&mut arg.value);
+            }
+        }
+        Expr::Lambda(_, _, body) => fold_constants(body),
+        _ => {}
+    }
+
+    let folded = match expr {
+        Expr::Unary(UnaryOp::Minus, e, _) => match **e {
+            Expr::Int(i) => i.checked_neg().map(Expr::Int),
+            Expr::Float(f) => Some(Expr::Float(-f)),
+            _ => None,
+        },
+        Expr::Binary(bop, l, r, _) => fold_binary(*bop, l, r),
+        _ => None,
+    };
+    if let Some(folded) = folded {
+        *expr = folded;
+    }
+}
+
+fn fold_binary(bop: BinaryOp, l: &Expr, r: &Expr) -> Option<Expr> {
+    use BinaryOp::*;
+    match (bop, l, r) {
+        (Add, Expr::Int(a), Expr::Int(b)) => a.checked_add(*b).map(Expr::Int),
+        (Sub, Expr::Int(a), Expr::Int(b)) => a.checked_sub(*b).map(Expr::Int),
+        (Mul, Expr::Int(a), Expr::Int(b)) => a.checked_mul(*b).map(Expr::Int),
+        (Div, Expr::Int(a), Expr::Int(b)) if *b != 0 => a.checked_div(*b).map(Expr::Int),
+        (Add | Sub | Mul | Div, _, _) => {
+            let (a, b) = (as_float(l)?, as_float(r)?);
+            Some(Expr::Float(match bop {
+                Add => a + b,
+                Sub => a - b,
+                Mul => a * b,
+                Div if b != 0.0 => a / b,
+                _ => return None,
+            }))
+        }
+        _ => None,
+    }
+}
+
+#[inline(always)]
+fn as_float(e: &Expr) -> Option<f64> {
+    match e {
+        Expr::Int(i) => Some(*i as f64),
+        Expr::Float(f) => Some(*f),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Span;
+
+    fn binary(bop: BinaryOp, l: Expr, r: Expr) -> Expr {
+        Expr::Binary(bop, Box::new(l), Box::new(r), Span::default())
+    }
+
+    fn folded(e: Expr) -> Expr {
+        let mut stmts = vec![Stmt::Print(e)];
+        fold_constants(&mut stmts);
+        match stmts.pop() {
+            Some(Stmt::Print(e)) => e,
+            s => panic!("unexpected statement {:?}", s),
+        }
+    }
+
+    #[test]
+    fn fold_arithmetic() {
+        // 1 + 2 * 3
+        let e = binary(
+            BinaryOp::Add,
+            Expr::Int(1),
+            binary(BinaryOp::Mul, Expr::Int(2), Expr::Int(3)),
+        );
+        assert_eq!(folded(e), Expr::Int(7));
+
+        // -(1.5) - 2
+        let e = binary(
+            BinaryOp::Sub,
+            Expr::Unary(UnaryOp::Minus, Box::new(Expr::Float(1.5)), Span::default()),
+            Expr::Int(2),
+        );
+        assert_eq!(folded(e), Expr::Float(-3.5));
+    }
+
+    #[test]
+    fn leave_runtime_errors_unfolded() {
+        for e in [
+            binary(BinaryOp::Div, Expr::Int(1), Expr::Int(0)),
+            binary(BinaryOp::Div, Expr::Float(1.0), Expr::Float(0.0)),
+            binary(BinaryOp::Add, Expr::Int(i64::MAX), Expr::Int(1)),
+            binary(BinaryOp::Sub, Expr::Int(1), Expr::String("a".into())),
+        ] {
+            assert_eq!(folded(e.clone()), e);
+        }
+
+        // The operand is still folded when the outer operation is not.
+        let e = binary(
+            BinaryOp::Div,
+            Expr::Int(1),
+            binary(BinaryOp::Sub, Expr::Int(2), Expr::Int(2)),
+        );
+        assert_eq!(folded(e), binary(BinaryOp::Div, Expr::Int(1), Expr::Int(0)));
+    }
+}
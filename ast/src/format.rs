@@ -0,0 +1,382 @@
+use crate::ast::*;
+
+const INDENT: &str = "  ";
+
+/// Pretty prints a program back into canonical, re-parseable Lox source.
+pub fn format_source(stmts: &[Stmt]) -> String {
+    let mut formatter = Formatter::new(0);
+    for stmt in stmts {
+        formatter.stmt(stmt);
+    }
+    formatter.out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum Precedence {
+    Assignment,
+    Or,
+    And,
+    Equality,
+    Comparison,
+    Term,
+    Factor,
+    Unary,
+    Call,
+    Primary,
+}
+
+impl Precedence {
+    #[inline(always)]
+    fn of(bop: BinaryOp) -> Self {
+        use BinaryOp::*;
+        match bop {
+            Or => Precedence::Or,
+            And => Precedence::And,
+            Eq | Ne => Precedence::Equality,
+            Lt | Gt | Le | Ge => Precedence::Comparison,
+            Add | Sub => Precedence::Term,
+            Mul | Div => Precedence::Factor,
+        }
+    }
+
+    #[inline(always)]
+    fn next(self) -> Self {
+        use Precedence::*;
+        match self {
+            Assignment => Or,
+            Or => And,
+            And => Equality,
+            Equality => Comparison,
+            Comparison => Term,
+            Term => Factor,
+            Factor => Unary,
+            Unary => Call,
+            Call | Primary => Primary,
+        }
+    }
+}
+
+struct Formatter {
+    out: String,
+    depth: usize,
+}
+
+impl Formatter {
+    fn new(depth: usize) -> Self {
+        Self {
+            out: String::new(),
+            depth,
+        }
+    }
+
+    fn indent(&mut self) {
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    fn line(&mut self, line: &str) {
+        self.indent();
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+
+    fn block(&mut self, stmts: &[Stmt]) {
+        self.depth += 1;
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+        self.depth -= 1;
+    }
+
+    /// Writes `body` after an already emitted clause head such as `if (cond)`.
+    fn clause(&mut self, body: &Stmt) {
+        match body {
+            Stmt::Block(stmts) => {
+                self.out.push_str(" {\n");
+                self.block(stmts);
+                self.indent();
+                self.out.push_str("}\n");
+            }
+            stmt => {
+                self.out.push('\n');
+                self.depth += 1;
+                self.stmt(stmt);
+                self.depth -= 1;
+            }
+        }
+    }
+
+    fn function(
+        &mut self,
+        head: &str,
+        params: &[Identifier],
+        annotations: &Annotations,
+        body: &[Stmt],
+    ) {
+        let params = params_list(params, annotations);
+        self.line(&format!("{}({}) {{", head, params));
+        self.block(body);
+        self.line("}");
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Print(e) => {
+                let e = self.expr(e, Precedence::Assignment);
+                self.line(&format!("print {};", e));
+            }
+            Stmt::Expr(e) => {
+                let s = self.expr(e, Precedence::Assignment);
+                if starts_with_lambda(e) {
+                    self.line(&format!("({});", s));
+                } else {
+                    self.line(&format!("{};", s));
+                }
+            }
+            Stmt::VariableDecl(VariableDecl { name, definition }) => match definition {
+                Some(definition) => {
+                    let definition = self.expr(definition, Precedence::Assignment);
+                    self.line(&format!("var {} = {};", name, definition));
+                }
+                None => self.line(&format!("var {};", name)),
+            },
+            Stmt::Block(stmts) => {
+                self.line("{");
+                self.block(stmts);
+                self.line("}");
+            }
+            Stmt::Conditional(Conditional {
+                cond,
+                if_branch,
+                else_branch,
+            }) => {
+                let cond = self.expr(cond, Precedence::Assignment);
+                self.indent();
+                self.out.push_str(&format!("if ({})", cond));
+                self.clause(if_branch);
+                if let Some(else_branch) = else_branch {
+                    if let Stmt::Block(_) = **if_branch {
+                        self.out.pop();
+                        self.out.push_str(" else");
+                    } else {
+                        self.indent();
+                        self.out.push_str("else");
+                    }
+                    self.clause(else_branch);
+                }
+            }
+            Stmt::Loop(Loop {
+                cond,
+                body,
+                update: None,
+            }) => {
+                let cond = self.expr(cond, Precedence::Assignment);
+                self.indent();
+                self.out.push_str(&format!("while ({})", cond));
+                self.clause(body);
+            }
+            Stmt::Loop(Loop {
+                cond,
+                body,
+                update: Some(update),
+            }) => {
+                let cond = self.expr(cond, Precedence::Assignment);
+                let update = self.expr(update, Precedence::Assignment);
+                self.indent();
+                self.out.push_str(&format!("for (; {}; {})", cond, update));
+                self.clause(body);
+            }
+            Stmt::ForEach(ForEach {
+                name,
+                iterable,
+                body,
+            }) => {
+                let iterable = self.expr(iterable, Precedence::Assignment);
+                self.indent();
+                self.out
+                    .push_str(&format!("for (var {} in {})", name, iterable));
+                self.clause(body);
+            }
+            Stmt::FunctionDecl(FunctionDecl {
+                name,
+                params,
+                annotations,
+                body,
+            }) => self.function(&format!("fun {}", name), params, annotations, body),
+            Stmt::Return(Expr::Nil, _) => self.line("return;"),
+            Stmt::Return(e, _) => {
+                let e = self.expr(e, Precedence::Assignment);
+                self.line(&format!("return {};", e));
+            }
+            Stmt::Break(_) => self.line("break;"),
+            Stmt::Continue(_) => self.line("continue;"),
+            Stmt::Import(Import {
+                path, alias: None, ..
+            }) => self.line(&format!("import \"{}\";", path)),
+            Stmt::Import(Import {
+                path,
+                alias: Some(alias),
+                ..
+            }) => self.line(&format!("import \"{}\" as {};", path, alias.token.lexeme)),
+            Stmt::ClassDecl(ClassDecl {
+                name,
+                super_class,
+                methods,
+            }) => {
+                match super_class {
+                    Some(super_class) => {
+                        let super_class = self.expr(super_class, Precedence::Primary);
+                        self.line(&format!("class {} < {} {{", name, super_class));
+                    }
+                    None => self.line(&format!("class {} {{", name)),
+                }
+                self.depth += 1;
+                for method in methods {
+                    self.function(
+                        &method.name.to_string(),
+                        &method.params,
+                        &method.annotations,
+                        &method.body,
+                    );
+                }
+                self.depth -= 1;
+                self.line("}");
+            }
+        }
+    }
+
+    fn expr(&self, expr: &Expr, min: Precedence) -> String {
+        let (s, precedence) = match expr {
+            Expr::Nil => ("nil".into(), Precedence::Primary),
+            Expr::Int(i) if *i < 0 => (i.to_string(), Precedence::Unary),
+            Expr::Int(i) => (i.to_string(), Precedence::Primary),
+            Expr::Float(f) if f.is_finite() && f.fract() == 0.0 => {
+                (format!("{:.1}", f), float_precedence(*f))
+            }
+            Expr::Float(f) => (f.to_string(), float_precedence(*f)),
+            Expr::Boolean(b) => (b.to_string(), Precedence::Primary),
+            Expr::Ident(id) | Expr::This(id) => (id.to_string(), Precedence::Primary),
+            Expr::String(s) => (string_literal(s), Precedence::Primary),
+            Expr::Interpolation(e) => (
+                format!("\"${{{}}}\"", self.expr(e, Precedence::Assignment)),
+                Precedence::Primary,
+            ),
+            Expr::Unary(uop, e, _) => (
+                format!("{}{}", uop, self.expr(e, Precedence::Unary)),
+                Precedence::Unary,
+            ),
+            Expr::Binary(bop, e1, e2, _) | Expr::Logical(bop, e1, e2, _) => {
+                let precedence = Precedence::of(*bop);
+                (
+                    format!(
+                        "{} {} {}",
+                        self.expr(e1, precedence),
+                        bop,
+                        self.expr(e2, precedence.next())
+                    ),
+                    precedence,
+                )
+            }
+            Expr::Assign(target, value, _) => (
+                format!(
+                    "{} = {}",
+                    self.expr(target, Precedence::Call),
+                    self.expr(value, Precedence::Assignment)
+                ),
+                Precedence::Assignment,
+            ),
+            Expr::Call(callee, args, _) => (
+                format!(
+                    "{}({})",
+                    self.expr(callee, Precedence::Call),
+                    args.iter()
+                        .map(|arg| self.expr(&arg.value, Precedence::Assignment))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                Precedence::Call,
+            ),
+            Expr::Lambda(params, annotations, body) => {
+                let mut formatter = Formatter::new(self.depth);
+                formatter.function("fun ", params, annotations, body);
+                let lambda = formatter.out.trim().to_string();
+                (lambda, Precedence::Primary)
+            }
+            Expr::Get(object, property) => (
+                format!("{}.{}", self.expr(object, Precedence::Call), property),
+                Precedence::Call,
+            ),
+            Expr::GetSafe(object, property) => (
+                format!("{}?.{}", self.expr(object, Precedence::Call), property),
+                Precedence::Call,
+            ),
+            Expr::Set(object, property, value) => (
+                format!(
+                    "{}.{} = {}",
+                    self.expr(object, Precedence::Call),
+                    property,
+                    self.expr(value, Precedence::Assignment)
+                ),
+                Precedence::Assignment,
+            ),
+            Expr::Super(_, method) => (format!("super.{}", method), Precedence::Primary),
+        };
+
+        if precedence < min {
+            format!("({})", s)
+        } else {
+            s
+        }
+    }
+}
+
+#[inline(always)]
+fn float_precedence(f: f64) -> Precedence {
+    if f.is_sign_negative() {
+        Precedence::Unary
+    } else {
+        Precedence::Primary
+    }
+}
+
+/// A string holding a `"` can only be written raw, with enough `#`s that no `"` inside
+/// ends it early.
+fn string_literal(s: &str) -> String {
+    if !s.contains('"') {
+        return format!("\"{}\"", s.replace("${", "\\${"));
+    }
+    let hashes = (1..)
+        .map(|n| "#".repeat(n))
+        .find(|hashes| !s.contains(&format!("\"{}", hashes)))
+        .unwrap();
+    format!("r{0}\"{1}\"{0}", hashes, s)
+}
+
+#[inline(always)]
+fn params_list(params: &[Identifier], annotations: &Annotations) -> String {
+    params
+        .iter()
+        .enumerate()
+        .map(|(i, param)| match annotations.get(i) {
+            Some(Some(ty)) => format!("{}: {}", param, ty),
+            _ => param.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// An expression statement starting with `fun` would be parsed as a function declaration.
+fn starts_with_lambda(expr: &Expr) -> bool {
+    match expr {
+        Expr::Lambda(..) => true,
+        Expr::Binary(_, e, _, _)
+        | Expr::Logical(_, e, _, _)
+        | Expr::Assign(e, _, _)
+        | Expr::Call(e, _, _)
+        | Expr::Get(e, _)
+        | Expr::GetSafe(e, _)
+        | Expr::Set(e, _, _) => starts_with_lambda(e),
+        _ => false,
+    }
+}
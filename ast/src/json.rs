@@ -0,0 +1,6 @@
+use crate::ast::*;
+
+/// Serializes a parsed program to JSON, tagging every node with its variant name.
+pub fn to_json(stmts: &[Stmt]) -> String {
+    serde_json::to_string(stmts).expect("AST is always serializable")
+}
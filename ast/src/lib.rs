@@ -5,14 +5,37 @@ extern crate lox_lexer;
 use lox_lexer::Span;
 use lox_lexer::TokenType;
 
+extern crate anyhow;
+
 extern crate thiserror;
 use thiserror::Error;
 
 mod ast;
 pub use ast::*;
 
+mod coerce;
+pub use coerce::*;
+
 mod env;
 use env::EnvInner;
+
+mod fold;
+pub use fold::fold_constants;
+
+mod format;
+pub use format::format_source;
+
+mod json;
+pub use json::to_json;
+
+mod locate;
+pub use locate::{node_at, NodeRef};
+
+mod metrics;
+pub use metrics::{metrics, ProgramMetrics};
+
+mod visit;
+pub use visit::*;
 pub type Env = Rc<RefCell<EnvInner>>;
 
 #[inline(always)]
@@ -25,6 +48,11 @@ pub fn push_env(env: Env) -> Env {
     Rc::new(RefCell::new(EnvInner::detach_env(env)))
 }
 
+#[inline(always)]
+pub fn push_params_env(env: Env, params: Rc<Vec<Identifier>>, args: Vec<Object>) -> Env {
+    Rc::new(RefCell::new(EnvInner::with_params(env, params, args)))
+}
+
 #[inline(always)]
 pub fn pop_env(env: Env) -> Env {
     env.borrow()
@@ -48,7 +76,7 @@ pub fn assign_env(env: &EnvInner, id: &Identifier, up: usize, value: Object) ->
 
 #[derive(Debug, Error)]
 pub enum EnvErrorKind {
-    #[error("Error: Undefined variable '{0}'.")]
+    #[error("Undefined variable '{0}'.")]
     UndefinedVariable(Identifier),
 
     #[error("Error: Enclosing environment does not exist.")]
@@ -58,4 +86,22 @@ pub enum EnvErrorKind {
     UndefinedProperty(String),
 }
 
+impl EnvErrorKind {
+    /// Position of the undefined name, when the error carries one.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            EnvErrorKind::UndefinedVariable(id) => Some(id.token.span),
+            _ => None,
+        }
+    }
+}
+
+/// Returned by the `Object::try_into_*` conversions when the value has another type.
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("Expected {expected} but got {found}.")]
+pub struct ConversionError {
+    pub expected: &'static str,
+    pub found: &'static str,
+}
+
 type Result<T> = std::result::Result<T, EnvErrorKind>;
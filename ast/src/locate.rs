@@ -0,0 +1,164 @@
+use lox_lexer::Token;
+
+use crate::ast::*;
+use crate::Span;
+
+/// A statement or expression found by `node_at`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NodeRef<'a> {
+    Stmt(&'a Stmt),
+    Expr(&'a Expr),
+}
+
+/// The innermost node with a token at `span`, e.g. the `Expr::Ident` for a position inside
+/// a variable name. Only nodes that keep tokens can be found: names, `this`, `super` and
+/// operators, but not literals. A statement is returned for the name it declares.
+pub fn node_at(stmts: &[Stmt], span: Span) -> Option<NodeRef<'_>> {
+    stmts.iter().find_map(|stmt| stmt_at(stmt, span))
+}
+
+fn stmt_at(stmt: &Stmt, span: Span) -> Option<NodeRef<'_>> {
+    let declares = |name: &Identifier| covers(&name.token, span);
+    match stmt {
+        Stmt::Print(e) | Stmt::Expr(e) | Stmt::Return(e, _) => expr_at(e, span),
+        Stmt::VariableDecl(VariableDecl { name, definition }) => definition
+            .as_ref()
+            .and_then(|e| expr_at(e, span))
+            .or_else(|| declares(name).then_some(NodeRef::Stmt(stmt))),
+        Stmt::Block(stmts) => node_at(stmts, span),
+        Stmt::Conditional(Conditional {
+            cond,
+            if_branch,
+            else_branch,
+        }) => expr_at(cond, span)
+            .or_else(|| stmt_at(if_branch, span))
+            .or_else(|| else_branch.as_ref().and_then(|e| stmt_at(e, span))),
+        Stmt::Loop(Loop { cond, body, update }) => expr_at(cond, span)
+            .or_else(|| stmt_at(body, span))
+            .or_else(|| update.as_ref().and_then(|e| expr_at(e, span))),
+        Stmt::ForEach(ForEach {
+            name,
+            iterable,
+            body,
+        }) => expr_at(iterable, span)
+            .or_else(|| stmt_at(body, span))
+            .or_else(|| declares(name).then_some(NodeRef::Stmt(stmt))),
+        Stmt::FunctionDecl(FunctionDecl {
+            name, params, body, ..
+        }) => node_at(body, span).or_else(|| {
+            (declares(name) || params.iter().any(declares)).then_some(NodeRef::Stmt(stmt))
+        }),
+        Stmt::ClassDecl(ClassDecl {
+            name,
+            super_class,
+            methods,
+        }) => super_class
+            .as_ref()
+            .and_then(|e| expr_at(e, span))
+            .or_else(|| {
+                methods.iter().find_map(|method| {
+                    node_at(&method.body, span).or_else(|| {
+                        (declares(&method.name) || method.params.iter().any(declares))
+                            .then_some(NodeRef::Stmt(stmt))
+                    })
+                })
+            })
+            .or_else(|| declares(name).then_some(NodeRef::Stmt(stmt))),
+        Stmt::Import(Import { alias, .. }) => alias
+            .as_ref()
+            .filter(|alias| declares(alias))
+            .map(|_| NodeRef::Stmt(stmt)),
+        Stmt::Break(_) | Stmt::Continue(_) => None,
+    }
+}
+
+fn expr_at(expr: &Expr, span: Span) -> Option<NodeRef<'_>> {
+    let inner = match expr {
+        Expr::Nil
+        | Expr::Int(_)
+        | Expr::Float(_)
+        | Expr::Boolean(_)
+        | Expr::String(_)
+        | Expr::Ident(_)
+        | Expr::This(_)
+        | Expr::Super(_, _) => None,
+        Expr::Unary(_, e, _) | Expr::Interpolation(e) | Expr::Get(e, _) | Expr::GetSafe(e, _) => {
+            expr_at(e, span)
+        }
+        Expr::Binary(_, l, r, _) | Expr::Logical(_, l, r, _) | Expr::Assign(l, r, _) => {
+            expr_at(l, span).or_else(|| expr_at(r, span))
+        }
+        Expr::Set(object, _, value) => expr_at(object, span).or_else(|| expr_at(value, span)),
+        Expr::Call(callee, args, _) => {
+            expr_at(callee, span).or_else(|| args.iter().find_map(|arg| expr_at(&arg.value, span)))
+        }
+        Expr::Lambda(_, _, body) => node_at(body, span),
+    };
+    let own = match expr {
+        Expr::Ident(id) | Expr::This(id) => covers(&id.token, span),
+        Expr::Get(_, name) | Expr::GetSafe(_, name) | Expr::Set(_, name, _) => {
+            covers(&name.token, span)
+        }
+        Expr::Super(keyword, method) => covers(&keyword.token, span) || covers(&method.token, span),
+        Expr::Unary(op, _, at) => covers_text(*at, &op.to_string(), span),
+        Expr::Binary(op, _, _, at) | Expr::Logical(op, _, _, at) => {
+            covers_text(*at, &op.to_string(), span)
+        }
+        Expr::Lambda(params, _, _) => params.iter().any(|param| covers(&param.token, span)),
+        _ => false,
+    };
+    inner.or_else(|| own.then_some(NodeRef::Expr(expr)))
+}
+
+#[inline(always)]
+fn covers(token: &Token, span: Span) -> bool {
+    covers_text(token.span, &token.lexeme, span)
+}
+
+fn covers_text(at: Span, text: &str, span: Span) -> bool {
+    at.line == span.line && (at.col..at.col + text.chars().count()).contains(&span.col)
+}
+
+#[cfg(test)]
+mod tests {
+    use lox_lexer::TokenType;
+
+    use super::*;
+
+    fn ident(name: &str, line: usize, col: usize) -> Identifier {
+        Token::new_with_lexeme(TokenType::Ident, name, Span::new(line, col)).into()
+    }
+
+    #[test]
+    fn find_identifier() {
+        // var total = 1;
+        // print total + count;
+        let program = vec![
+            Stmt::VariableDecl(VariableDecl {
+                name: ident("total", 1, 5),
+                definition: Some(Expr::Int(1)),
+            }),
+            Stmt::Print(Expr::Binary(
+                BinaryOp::Add,
+                Box::new(Expr::Ident(ident("total", 2, 7))),
+                Box::new(Expr::Ident(ident("count", 2, 15))),
+                Span::new(2, 13),
+            )),
+        ];
+
+        let found = node_at(&program, Span::new(2, 17));
+        assert!(
+            matches!(found, Some(NodeRef::Expr(Expr::Ident(id))) if id.token.lexeme == "count")
+        );
+        assert!(matches!(
+            node_at(&program, Span::new(2, 13)),
+            Some(NodeRef::Expr(Expr::Binary(..)))
+        ));
+        assert!(matches!(
+            node_at(&program, Span::new(1, 9)),
+            Some(NodeRef::Stmt(Stmt::VariableDecl(_)))
+        ));
+        assert_eq!(node_at(&program, Span::new(2, 12)), None);
+        assert_eq!(node_at(&program, Span::new(2, 20)), None);
+    }
+}
@@ -0,0 +1,132 @@
+use crate::ast::*;
+use crate::visit::*;
+
+/// Size and shape of a program, as returned by [`metrics`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ProgramMetrics {
+    pub statements: usize,
+    pub expressions: usize,
+    /// Named functions, including class methods.
+    pub function_decls: usize,
+    pub class_decls: usize,
+    /// Deepest statement nesting; top-level statements are at depth 1.
+    pub max_depth: usize,
+}
+
+pub fn metrics(stmts: &[Stmt]) -> ProgramMetrics {
+    let mut counter = MetricsCounter::default();
+    for stmt in stmts {
+        counter.visit_stmt(stmt);
+    }
+    counter.metrics
+}
+
+#[derive(Default)]
+struct MetricsCounter {
+    metrics: ProgramMetrics,
+    depth: usize,
+}
+
+impl ExprVisitor for MetricsCounter {
+    fn visit_expr(&mut self, expr: &Expr) {
+        self.metrics.expressions += 1;
+        walk_expr(self, expr)
+    }
+
+    fn visit_lambda_body(&mut self, body: &[Stmt]) {
+        for stmt in body {
+            self.visit_stmt(stmt);
+        }
+    }
+}
+
+impl StmtVisitor for MetricsCounter {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        self.metrics.statements += 1;
+        match stmt {
+            Stmt::FunctionDecl(_) => self.metrics.function_decls += 1,
+            Stmt::ClassDecl(ClassDecl { methods, .. }) => {
+                self.metrics.class_decls += 1;
+                self.metrics.function_decls += methods.len();
+            }
+            _ => {}
+        }
+
+        self.depth += 1;
+        self.metrics.max_depth = self.metrics.max_depth.max(self.depth);
+        walk_stmt(self, stmt);
+        self.depth -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lox_lexer::Token;
+
+    use super::*;
+    use crate::Span;
+    use crate::TokenType;
+
+    fn ident(name: &str) -> Identifier {
+        Token::new_with_lexeme(TokenType::Ident, name, Span::default()).into()
+    }
+
+    fn function(name: &str, body: Vec<Stmt>) -> FunctionDecl {
+        FunctionDecl {
+            name: ident(name),
+            params: vec![],
+            annotations: vec![],
+            body,
+        }
+    }
+
+    #[test]
+    fn count_program() {
+        // fun f() { if (true) { return 1 + 2; } }
+        // class A { m() { print nil; } }
+        // var x = fun () { return x; };
+        let program = vec![
+            Stmt::FunctionDecl(function(
+                "f",
+                vec![Stmt::Conditional(Conditional {
+                    cond: Expr::Boolean(true),
+                    if_branch: Box::new(Stmt::Block(vec![Stmt::Return(
+                        Expr::Binary(
+                            BinaryOp::Add,
+                            Box::new(Expr::Int(1)),
+                            Box::new(Expr::Int(2)),
+                            Span::default(),
+                        ),
+                        Span::default(),
+                    )])),
+                    else_branch: None,
+                })],
+            )),
+            Stmt::ClassDecl(ClassDecl {
+                name: ident("A"),
+                super_class: None,
+                methods: vec![function("m", vec![Stmt::Print(Expr::Nil)])],
+            }),
+            Stmt::VariableDecl(VariableDecl {
+                name: ident("x"),
+                definition: Some(Expr::Lambda(
+                    vec![],
+                    vec![],
+                    vec![Stmt::Return(Expr::Ident(ident("x")), Span::default())],
+                )),
+            }),
+        ];
+
+        assert_eq!(
+            metrics(&program),
+            ProgramMetrics {
+                statements: 8,
+                expressions: 7,
+                function_decls: 2,
+                class_decls: 1,
+                max_depth: 4,
+            }
+        );
+        assert_eq!(metrics(&[]), ProgramMetrics::default());
+    }
+}
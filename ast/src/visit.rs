@@ -0,0 +1,167 @@
+use crate::ast::*;
+
+/// Visits expressions. Override `visit_expr` to inspect nodes and call `walk_expr` to
+/// keep descending into children.
+pub trait ExprVisitor {
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr)
+    }
+
+    /// Called with the body of each lambda. By default the expressions inside it are
+    /// visited; a `StmtVisitor` can forward the statements to `visit_stmt` instead.
+    fn visit_lambda_body(&mut self, body: &[Stmt]) {
+        for stmt in body {
+            walk_stmt_exprs(self, stmt);
+        }
+    }
+}
+
+/// Visits statements and, through `ExprVisitor`, the expressions inside them.
+pub trait StmtVisitor: ExprVisitor {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        walk_stmt(self, stmt)
+    }
+}
+
+pub fn walk_expr<V: ExprVisitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match expr {
+        Expr::Nil
+        | Expr::Int(_)
+        | Expr::Float(_)
+        | Expr::Boolean(_)
+        | Expr::Ident(_)
+        | Expr::String(_)
+        | Expr::This(_)
+        | Expr::Super(_, _) => {}
+        Expr::Unary(_, e, _) | Expr::Interpolation(e) | Expr::Get(e, _) | Expr::GetSafe(e, _) => {
+            visitor.visit_expr(e)
+        }
+        Expr::Binary(_, l, r, _) | Expr::Logical(_, l, r, _) | Expr::Assign(l, r, _) => {
+            visitor.visit_expr(l);
+            visitor.visit_expr(r);
+        }
+        Expr::Set(object, _, value) => {
+            visitor.visit_expr(object);
+            visitor.visit_expr(value);
+        }
+        Expr::Call(callee, args, _) => {
+            visitor.visit_expr(callee);
+            for arg in args {
+                visitor.visit_expr(&arg.value);
+            }
+        }
+        Expr::Lambda(_, _, body) => visitor.visit_lambda_body(body),
+    }
+}
+
+pub fn walk_stmt<V: StmtVisitor + ?Sized>(visitor: &mut V, stmt: &Stmt) {
+    let (exprs, stmts) = children(stmt);
+    for expr in exprs {
+        visitor.visit_expr(expr);
+    }
+    for stmt in stmts {
+        visitor.visit_stmt(stmt);
+    }
+}
+
+fn walk_stmt_exprs<V: ExprVisitor + ?Sized>(visitor: &mut V, stmt: &Stmt) {
+    let (exprs, stmts) = children(stmt);
+    for expr in exprs {
+        visitor.visit_expr(expr);
+    }
+    for stmt in stmts {
+        walk_stmt_exprs(visitor, stmt);
+    }
+}
+
+/// The expressions and statements directly nested in `stmt`.
+fn children(stmt: &Stmt) -> (Vec<&Expr>, Vec<&Stmt>) {
+    match stmt {
+        Stmt::Print(e) | Stmt::Expr(e) | Stmt::Return(e, _) => (vec![e], vec![]),
+        Stmt::VariableDecl(VariableDecl { definition, .. }) => {
+            (definition.iter().collect(), vec![])
+        }
+        Stmt::Block(stmts) => (vec![], stmts.iter().collect()),
+        Stmt::Conditional(Conditional {
+            cond,
+            if_branch,
+            else_branch,
+        }) => {
+            let mut stmts = vec![if_branch.as_ref()];
+            stmts.extend(else_branch.as_deref());
+            (vec![cond], stmts)
+        }
+        Stmt::Loop(Loop { cond, body, update }) => {
+            let mut exprs = vec![cond];
+            exprs.extend(update);
+            (exprs, vec![body])
+        }
+        Stmt::ForEach(ForEach { iterable, body, .. }) => (vec![iterable], vec![body]),
+        Stmt::FunctionDecl(FunctionDecl { body, .. }) => (vec![], body.iter().collect()),
+        Stmt::ClassDecl(ClassDecl {
+            super_class,
+            methods,
+            ..
+        }) => (
+            super_class.iter().collect(),
+            methods.iter().flat_map(|method| &method.body).collect(),
+        ),
+        Stmt::Break(_) | Stmt::Continue(_) | Stmt::Import(_) => (vec![], vec![]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lox_lexer::Token;
+
+    use super::*;
+    use crate::Span;
+    use crate::TokenType;
+
+    struct CallCounter(usize);
+
+    impl ExprVisitor for CallCounter {
+        fn visit_expr(&mut self, expr: &Expr) {
+            if let Expr::Call(..) = expr {
+                self.0 += 1;
+            }
+            walk_expr(self, expr)
+        }
+    }
+
+    impl StmtVisitor for CallCounter {}
+
+    fn ident(name: &str) -> Expr {
+        Expr::Ident(Token::new_with_lexeme(TokenType::Ident, name, Span::default()).into())
+    }
+
+    fn call(callee: Expr, args: Vec<Expr>) -> Expr {
+        Expr::Call(
+            Box::new(callee),
+            args.into_iter().map(Argument::from).collect(),
+            Span::default(),
+        )
+    }
+
+    #[test]
+    fn count_calls() {
+        // f(g(1)); while (h()) { print fun () { return i(); }; }
+        let program = vec![
+            Stmt::Expr(call(ident("f"), vec![call(ident("g"), vec![Expr::Int(1)])])),
+            Stmt::Loop(Loop {
+                cond: call(ident("h"), vec![]),
+                body: Box::new(Stmt::Block(vec![Stmt::Print(Expr::Lambda(
+                    vec![],
+                    vec![],
+                    vec![Stmt::Return(call(ident("i"), vec![]), Span::default())],
+                ))])),
+                update: None,
+            }),
+        ];
+        let mut counter = CallCounter(0);
+        for stmt in &program {
+            counter.visit_stmt(stmt);
+        }
+        assert_eq!(counter.0, 4);
+    }
+}
@@ -86,6 +86,10 @@ impl CommandUnderTest {
         self
     }
 
+    pub fn stdout(&self) -> &str {
+        &self.stdout
+    }
+
     pub fn run(&mut self) -> ExitStatus {
         let mut child = self.raw.spawn().expect("failed to run command");
 
@@ -2,6 +2,8 @@
 use std::cell::RefCell;
 use std::io::Write;
 use std::rc::Rc;
+use std::time::Duration;
+use std::time::Instant;
 
 extern crate tempfile;
 use tempfile::NamedTempFile;
@@ -58,3 +60,31 @@ pub fn bench_cmd(mut cmd: CommandUnderTest, args: &[&str]) {
         .exit_ok()
         .expect("Error: Command did not run successfully.")
 }
+
+/// Like `bench_cmd`, but hands back the program's stdout and the wall time it took so a
+/// benchmark can check it still computes the right answer.
+pub fn run_capturing(mut cmd: CommandUnderTest, args: &[&str]) -> (String, Duration) {
+    let cmd = cmd.args(args);
+    let start = Instant::now();
+    cmd.run()
+        .exit_ok()
+        .expect("Error: Command did not run successfully.");
+    let elapsed = start.elapsed();
+    (cmd.stdout().to_string(), elapsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capture_fib_output() {
+        let fin = tif(fib_program!(num_iter = 10));
+        let (stdout, elapsed) = run_capturing(
+            CommandUnderTest::new("interpreter_main".to_string()),
+            &[fin.path().to_str().unwrap()],
+        );
+        assert_eq!(stdout, "55\n");
+        assert!(elapsed > Duration::ZERO);
+    }
+}
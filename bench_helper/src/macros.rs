@@ -762,3 +762,40 @@ $(
         )
     }};
 }
+
+#[macro_export]
+macro_rules! closures_program {
+  ($($e:tt)*) => {{
+        format!(
+r#"
+fun makeCounter(start) {{
+  var count = start;
+  fun counter() {{
+    count = count + 1;
+    return count;
+  }}
+  return counter;
+}}
+
+var sum = 0;
+for (var i = 0; i < {num_iter}; i = i + 1) {{
+  var outer = i;
+  fun add(x) {{
+    fun inner() {{
+      return x + outer;
+    }}
+    return inner;
+  }}
+  var counter = makeCounter(i);
+  counter();
+  sum = sum + add(i)() + counter();
+}}
+
+print sum;
+"#,
+$(
+    $e
+)*
+        )
+    }};
+}
@@ -0,0 +1,21 @@
+use bench_helper::bench_cmd;
+use bench_helper::closures_program;
+use bench_helper::tif;
+use bench_helper::CommandUnderTest;
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::BenchmarkId;
+use criterion::Criterion;
+use paste::paste;
+
+use benches::generate_bench;
+
+generate_bench!(closures, "lox-rs", "interpreter_main", closures_program!, [100000]);
+
+criterion_group! {
+    name = closures_benches;
+    config = Criterion::default().sample_size(10);
+    targets = closures_bench_fn,
+}
+
+criterion_main!(closures_benches);
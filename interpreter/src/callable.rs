@@ -8,6 +8,7 @@ use lexer::TokenType;
 
 use crate::anyhow;
 use crate::ast::*;
+use crate::native;
 use crate::ErrorOrCtxJmp;
 use crate::EvalResult;
 use crate::Interpreter;
@@ -35,84 +36,100 @@ impl Arity for ClassObject {
     }
 }
 
+impl Arity for NativeFunction {
+    #[inline(always)]
+    fn arity(&self) -> Result<usize> {
+        Ok(self.arity)
+    }
+}
+
+impl Arity for HostFunction {
+    #[inline(always)]
+    fn arity(&self) -> Result<usize> {
+        Ok(self.arity)
+    }
+}
+
 impl Arity for Object {
     #[inline(always)]
     fn arity(&self) -> Result<usize> {
         match self {
             Object::Function(f) => f.arity(),
+            Object::NativeFunction(nf) => nf.arity(),
+            Object::HostFunction(hf) => hf.arity(),
             Object::Class(c) => c.arity(),
-            _ => {
-                return Err(ErrorOrCtxJmp::Error(anyhow!(
-                    "Can only call functions and classes.",
-                )));
-            }
+            _ => Err(not_callable()),
         }
     }
 }
 
-pub(crate) trait Callable<W>: Arity {
-    fn call(&self, args: Vec<Object>, ctx: &mut Interpreter<W>) -> EvalResult;
+pub(crate) trait Callable<W, E>: Arity {
+    fn call(&self, args: Vec<Object>, ctx: &mut Interpreter<W, E>) -> EvalResult;
 }
 
-impl<W: Write> Callable<W> for FuncObject {
-    #[inline(always)]
-    fn call(&self, args: Vec<Object>, ctx: &mut Interpreter<W>) -> EvalResult {
+impl<W: Write, E: Write> Callable<W, E> for FuncObject {
+    fn call(&self, mut args: Vec<Object>, ctx: &mut Interpreter<W, E>) -> EvalResult {
         if args.len() != self.params.len() {
-            return Err(ErrorOrCtxJmp::Error(anyhow!(
-                "Expected {} arguments but got {}.",
-                self.params.len(),
-                args.len()
-            )));
+            return Err(arity_mismatch(self.params.len(), args.len()));
         }
 
         ctx.save_env(Rc::clone(&self.closure));
-        ctx.push_scope();
-
-        for (param, arg) in self
-            .params
-            .as_ref()
-            .clone()
-            .into_iter()
-            .zip(args.into_iter())
-        {
-            ctx.env.borrow_mut().init_variable(param, arg);
-        }
-
-        let mut function_result = match ctx.run_many(&self.body) {
-            Ok(()) => Object::Nil,
-            Err(ErrorOrCtxJmp::RetJump { object }) => object,
-            e => {
-                e?;
-                Object::Nil
+        let enclosing_function = ctx
+            .current_function
+            .replace((Rc::clone(&self.body), Rc::clone(&self.closure)));
+
+        // A `return` that calls this same function comes back as a `TailCall`, which
+        // reruns the body with the new arguments instead of growing the Rust stack. This
+        // frame stays on the stack for every level of ordinary recursion, so anything
+        // sizeable lives in the out-of-line helpers.
+        let function_result = loop {
+            let checked = match ctx.check_param_types {
+                true => check_param_types(self, &args),
+                false => Ok(()),
+            };
+            ctx.push_params_scope(Rc::clone(&self.params), std::mem::take(&mut args));
+            match checked.and_then(|_| ctx.run_many(&self.body)) {
+                Err(ErrorOrCtxJmp::TailCall { args: next }) => {
+                    ctx.env = Rc::clone(&self.closure);
+                    args = next;
+                }
+                Ok(()) => break Ok(Object::Nil),
+                Err(ErrorOrCtxJmp::RetJump { object }) => break Ok(object),
+                Err(e) => break Err(e),
             }
         };
+        ctx.current_function = enclosing_function;
 
-        if self.is_initializer {
-            function_result = get_env(
-                &ctx.env.borrow(),
-                &Token::new(TokenType::This, Span::default()).into(),
-                1,
-            )?
-            .borrow()
-            .clone();
-        }
+        let function_result = match function_result {
+            Ok(_) if self.is_initializer => initialized_this(ctx),
+            r => r,
+        };
 
+        // Restore the caller's environment on every path, a runtime error included.
         ctx.pop_scope();
         ctx.reset_env();
 
-        Ok(function_result)
+        function_result
     }
 }
 
-impl<W: Write> Callable<W> for ClassObject {
-    #[inline(always)]
-    fn call(&self, args: Vec<Object>, ctx: &mut Interpreter<W>) -> EvalResult {
+/// The instance an initializer returns, whatever its body returned.
+#[inline(never)]
+fn initialized_this<W, E>(ctx: &Interpreter<W, E>) -> EvalResult {
+    get_env(
+        &ctx.env.borrow(),
+        &Token::new(TokenType::This, Span::default()).into(),
+        1,
+    )
+    .map(|this| this.borrow().clone())
+    .map_err(ErrorOrCtxJmp::from)
+}
+
+impl<W: Write, E: Write> Callable<W, E> for ClassObject {
+    #[inline(never)]
+    fn call(&self, args: Vec<Object>, ctx: &mut Interpreter<W, E>) -> EvalResult {
         if args.len() != self.arity().unwrap() {
-            return Err(ErrorOrCtxJmp::Error(anyhow!(
-                "Expected {} arguments but got {}.",
-                self.arity().unwrap(),
-                args.len()
-            )));
+            return Err(arity_mismatch(self.arity().unwrap(), args.len()));
         }
         let instance = Rc::new(RefCell::new(ClassInstance::new(self.clone(), vec![])));
 
@@ -124,16 +141,78 @@ impl<W: Write> Callable<W> for ClassObject {
     }
 }
 
-impl<W: Write> Callable<W> for Object {
-    fn call(&self, args: Vec<Object>, ctx: &mut Interpreter<W>) -> EvalResult {
+impl<W: Write, E: Write> Callable<W, E> for NativeFunction {
+    #[inline(always)]
+    fn call(&self, args: Vec<Object>, ctx: &mut Interpreter<W, E>) -> EvalResult {
+        if args.len() != self.arity {
+            return Err(arity_mismatch(self.arity, args.len()));
+        }
+        native::call(self.name, args, ctx)
+    }
+}
+
+impl<W: Write, E: Write> Callable<W, E> for HostFunction {
+    #[inline(always)]
+    fn call(&self, args: Vec<Object>, _ctx: &mut Interpreter<W, E>) -> EvalResult {
+        if args.len() != self.arity {
+            return Err(arity_mismatch(self.arity, args.len()));
+        }
+        Ok((self.func)(args)?)
+    }
+}
+
+impl<W: Write, E: Write> Callable<W, E> for Object {
+    fn call(&self, args: Vec<Object>, ctx: &mut Interpreter<W, E>) -> EvalResult {
         match self {
             Object::Function(f) => f.call(args, ctx),
+            Object::NativeFunction(nf) => nf.call(args, ctx),
+            Object::HostFunction(hf) => hf.call(args, ctx),
             Object::Class(c) => c.call(args, ctx),
-            _ => {
+            _ => Err(not_callable()),
+        }
+    }
+}
+
+#[inline(never)]
+fn check_param_types(function: &FuncObject, args: &[Object]) -> Result<()> {
+    let annotations = match &function.annotations {
+        Some(annotations) => annotations,
+        None => return Ok(()),
+    };
+    for ((param, arg), annotation) in function.params.iter().zip(args).zip(annotations.iter()) {
+        if let Some(ty) = annotation {
+            if !matches_annotation(arg, ty) {
                 return Err(ErrorOrCtxJmp::Error(anyhow!(
-                    "Can only call functions and classes.",
+                    "Expected {} for parameter '{}' but got {}.",
+                    ty,
+                    param,
+                    arg.type_name()
                 )));
             }
         }
     }
+    Ok(())
+}
+
+/// `int`, `float` and `bool` narrow the runtime type names, `any` accepts everything.
+#[inline(always)]
+fn matches_annotation(o: &Object, ty: &str) -> bool {
+    match ty {
+        "any" => true,
+        "int" => matches!(o, Object::Int(_)),
+        "float" => matches!(o, Object::Float(_)),
+        "bool" => matches!(o, Object::Boolean(_)),
+        ty => o.type_name() == ty,
+    }
+}
+
+#[cold]
+#[inline(never)]
+fn arity_mismatch(expected: usize, got: usize) -> ErrorOrCtxJmp {
+    ErrorOrCtxJmp::Error(anyhow!("Expected {} arguments but got {}.", expected, got))
+}
+
+#[inline(always)]
+fn not_callable() -> ErrorOrCtxJmp {
+    ErrorOrCtxJmp::Error(anyhow!("Can only call functions and classes."))
 }
@@ -0,0 +1,131 @@
+use std::fmt::Display;
+use std::io::sink;
+
+use crate::lexer::Lexer;
+use crate::lexer::Span;
+use crate::parser::Parser;
+use crate::Interpreter;
+use crate::Resolver;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Option<Span>,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.span {
+            Some(span) => write!(f, "[line {}] {}", span.line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Everything the static phases found wrong with a program, in source order per phase.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Diagnostics {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    fn push(&mut self, severity: Severity, message: impl ToString, span: Option<Span>) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            message: message.to_string(),
+            span,
+        });
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+}
+
+/// Lexes, parses and resolves `source` without running it, carrying on past errors where
+/// a phase can: the lexer skips bad characters and the parser resumes at the next
+/// statement. The resolver still stops at its first error, and only runs on what parsed.
+pub fn analyze(source: &str) -> Diagnostics {
+    let mut diagnostics = Diagnostics::default();
+
+    let mut lexer = Lexer::new(source.chars()).unwrap();
+    let mut tokens = Vec::new();
+    while let Some(token) = lexer.next() {
+        match token {
+            Ok(token) => tokens.push(token),
+            Err(e) => diagnostics.push(Severity::Error, e, Some(lexer.span())),
+        }
+    }
+
+    let (mut stmts, errors) = Parser::new(tokens.into_iter()).program_with_recovery();
+    for e in errors {
+        let span = e.span();
+        diagnostics.push(Severity::Error, e, span);
+    }
+
+    let mut resolver = Resolver::new()
+        .with_condition_lint()
+        .with_unused_expression_lint();
+    if let Err(e) = resolver.resolve(&mut stmts, &mut Interpreter::new(sink())) {
+        diagnostics.push(Severity::Error, &e, e.span());
+    }
+    diagnostics
+        .diagnostics
+        .extend(resolver.warnings().iter().cloned());
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collect_errors_from_every_phase() {
+        let diagnostics = analyze("var a = 1 @;\nprint (;\n{\n  print b;\n  var b = 2;\n}\n1 + a;");
+        assert_eq!(
+            diagnostics
+                .diagnostics
+                .iter()
+                .map(Diagnostic::to_string)
+                .collect::<Vec<_>>(),
+            [
+                "[line 1] Error: Unexpected char '@' found in input.",
+                "[line 2] Error at ';': Expect expression.",
+                "[line 4] Undefined variable 'b'.",
+            ]
+        );
+        assert!(diagnostics.has_errors());
+        assert!(analyze("var a = 1;\nprint a;").is_empty());
+    }
+
+    #[test]
+    fn lint_warnings() {
+        let diagnostics = analyze("var a = 1;\na + 1;");
+        assert!(!diagnostics.has_errors());
+        assert_eq!(
+            diagnostics.diagnostics,
+            [Diagnostic {
+                severity: Severity::Warning,
+                message: "Warning: Expression statement has no effect.".into(),
+                span: Some(Span::new(2, 3)),
+            }]
+        );
+    }
+}
@@ -18,67 +18,67 @@ pub struct Evaluator;
 
 impl Evaluator {
     #[inline(always)]
-    pub fn evaluate<W: Write>(
+    pub fn evaluate<W: Write, E: Write>(
         expr: &Expr,
         env: Env,
-        interpreter: &mut Interpreter<W>,
+        interpreter: &mut Interpreter<W, E>,
     ) -> EvalResult {
         use BinaryOp::*;
         use Object::*;
         use UnaryOp::*;
+        interpreter.count_step();
         let r = match expr {
             Expr::Nil => Object::Nil,
             Expr::Int(i) => Object::Int(*i),
             Expr::Float(f) => Object::Float(*f),
             Expr::Boolean(b) => Object::Boolean(*b),
             Expr::String(s) => Object::String(s.clone()),
+            Expr::Interpolation(e) => Evaluator::interpolate(e, env, interpreter)?,
             Expr::Ident(ident) | Expr::This(ident) => {
                 let distance = interpreter.get_distance(ident);
                 get_env(&env.borrow(), ident, distance)?.borrow().clone()
             }
-            Expr::Unary(uop, expr) => match (uop, Evaluator::evaluate(expr, env, interpreter)?) {
-                (Minus, Int(i)) => Int(-i),
-                (Minus, Float(f)) => Float(-f),
-                (Not, object) => Boolean(!object.is_truth()),
-                (Minus, _) => {
-                    return Err(ErrorOrCtxJmp::Error(anyhow!("Operand must be a number.")));
-                }
-            },
-            Expr::Binary(bop, e1, e2) => {
-                match (
-                    bop,
-                    Evaluator::evaluate(e1, env.clone(), interpreter)?,
-                    Evaluator::evaluate(e2, env, interpreter)?,
-                ) {
-                    (Add, String(a), String(b)) => String(a + &b),
-                    (Add, Int(a), Int(b)) => Int(a + b),
-                    (Add, Int(a), Float(b)) => Float(a as f64 + b),
-                    (Sub, Int(a), Int(b)) => Int(a - b),
-                    (Sub, Int(a), Float(b)) => Float(a as f64 - b),
-                    (Mul, Int(a), Int(b)) => Int(a * b),
-                    (Mul, Int(a), Float(b)) => Float(a as f64 * b),
-                    (Div, Float(_) | Int(_), Int(0)) => {
-                        return Err(ErrorOrCtxJmp::Error(anyhow!("Cannot divide by 0.",)))
+            Expr::Unary(uop, expr, span) => {
+                match (uop, Evaluator::evaluate(expr, env, interpreter)?) {
+                    (Minus, Int(i)) => match i.checked_neg() {
+                        Some(i) => Int(i),
+                        None => return Err(runtime_error("Integer overflow.", *span)),
+                    },
+                    (Minus, Float(f)) => Float(-f),
+                    (Not, object) => Boolean(!interpreter.is_truth(&object)),
+                    (Minus, _) => {
+                        return Err(
+                            ErrorOrCtxJmp::Error(anyhow!("Operand must be a number.")).at(*span)
+                        );
                     }
-                    (Div, Float(_) | Int(_), Float(f)) if f == 0.0 => {
-                        return Err(ErrorOrCtxJmp::Error(anyhow!("Cannot divide by 0.",)))
-                    }
-                    (Div, Int(a), Int(b)) => Int(a / b),
-
-                    (Div, Int(a), Float(b)) => Float(a as f64 / b),
-                    (Add, Float(a), Int(b)) => Float(a + b as f64),
-                    (Add, Float(a), Float(b)) => Float(a + b),
-                    (Add, _, _) => {
-                        return Err(ErrorOrCtxJmp::Error(anyhow!(
-                            "Operands must be two numbers or two strings."
-                        )))
+                }
+            }
+            Expr::Binary(bop, e1, e2, span) => {
+                let o1 = Evaluator::evaluate(e1, env.clone(), interpreter)?;
+                let o2 = Evaluator::evaluate(e2, env, interpreter)?;
+                if let (Int(a), Int(b)) = (&o1, &o2) {
+                    if let Some(o) = int_binary(*bop, *a, *b) {
+                        return Ok(o);
                     }
-                    (Sub, Float(a), Int(b)) => Float(a - b as f64),
-                    (Sub, Float(a), Float(b)) => Float(a - b),
-                    (Mul, Float(a), Int(b)) => Float(a * b as f64),
-                    (Mul, Float(a), Float(b)) => Float(a * b),
-                    (Div, Float(a), Int(b)) => Float(a / b as f64),
-                    (Div, Float(a), Float(b)) => Float(a / b),
+                }
+                match (bop, o1, o2) {
+                    (Add, String(a), String(b)) => String(a + &b),
+                    (Add | Sub | Mul | Div, a, b) => match promote(&a, &b) {
+                        Some(numbers) => match arithmetic(*bop, numbers) {
+                            Ok(o) => o,
+                            Err(message) => return Err(runtime_error(message, *span)),
+                        },
+                        None if *bop == Add => {
+                            return Err(ErrorOrCtxJmp::Error(anyhow!(
+                                "Operands must be two numbers or two strings."
+                            ))
+                            .at(*span))
+                        }
+                        None => {
+                            return Err(ErrorOrCtxJmp::Error(anyhow!("Operands must be numbers."))
+                                .at(*span));
+                        }
+                    },
                     (Lt, Int(a), Int(b)) => Boolean(a < b),
                     (Gt, Int(a), Int(b)) => Boolean(a > b),
                     (Le, Int(a), Int(b)) => Boolean(a <= b),
@@ -87,10 +87,12 @@ impl Evaluator {
                     (Gt, Float(a), Float(b)) => Boolean(a > b),
                     (Le, Float(a), Float(b)) => Boolean(a <= b),
                     (Ge, Float(a), Float(b)) => Boolean(a >= b),
-                    (Eq, a, b) => Boolean(a == b),
-                    (Ne, a, b) => Boolean(a != b),
-                    (Sub | Mul | Div | Lt | Gt | Le | Ge, _, _) => {
-                        return Err(ErrorOrCtxJmp::Error(anyhow!("Operands must be numbers.")));
+                    (Eq, a, b) => Boolean(a.equals(&b)),
+                    (Ne, a, b) => Boolean(!a.equals(&b)),
+                    (Lt | Gt | Le | Ge, _, _) => {
+                        return Err(
+                            ErrorOrCtxJmp::Error(anyhow!("Operands must be numbers.")).at(*span)
+                        );
                     }
                     (bop, o1, o2) => {
                         return Err(ErrorOrCtxJmp::Error(anyhow!(
@@ -98,11 +100,12 @@ impl Evaluator {
                             bop,
                             o1,
                             o2
-                        )))
+                        ))
+                        .at(*span))
                     }
                 }
             }
-            Expr::Assign(ident, e) => {
+            Expr::Assign(ident, e, _) => {
                 let ident = if let Expr::Ident(ref ident) = **ident {
                     ident
                 } else {
@@ -113,10 +116,10 @@ impl Evaluator {
                 assign_env(&env.borrow(), ident, distance, value.clone())?;
                 value
             }
-            Expr::Logical(lop, e1, e2) => match lop {
+            Expr::Logical(lop, e1, e2, _) => match lop {
                 BinaryOp::And => {
                     let value = Evaluator::evaluate(e1, Rc::clone(&env), interpreter)?;
-                    if !value.is_truth() {
+                    if !interpreter.is_truth(&value) {
                         value
                     } else {
                         Evaluator::evaluate(e2, Rc::clone(&env), interpreter)?
@@ -124,7 +127,7 @@ impl Evaluator {
                 }
                 BinaryOp::Or => {
                     let value = Evaluator::evaluate(e1, Rc::clone(&env), interpreter)?;
-                    if value.is_truth() {
+                    if interpreter.is_truth(&value) {
                         value
                     } else {
                         Evaluator::evaluate(e2, Rc::clone(&env), interpreter)?
@@ -132,26 +135,27 @@ impl Evaluator {
                 }
                 _ => unreachable!(),
             },
-            Expr::Call(callee, args) => {
+            Expr::Call(callee, args, span) => {
                 let evaluated_args: Vec<Object> = args
                     .iter()
                     .map(|arg| Evaluator::evaluate(&arg.value, Rc::clone(&env), interpreter))
                     .collect::<Result<Vec<_>>>()?;
                 let callee = Evaluator::evaluate(callee, env, interpreter)?;
-                callee.call(evaluated_args, interpreter)?
+                callee
+                    .call(evaluated_args, interpreter)
+                    .map_err(|e| e.at(*span))?
             }
-            Expr::Lambda(params, body) => Object::Function(ast::FuncObject::new_lambda(
-                params.clone(),
-                body.clone(),
-                interpreter.env.clone(),
-            )),
-            Expr::Get(object, property) => match Evaluator::evaluate(object, env, interpreter)? {
-                Instance(i) => ClassInstance::get(&property.token.lexeme, i)?,
-                _ => {
-                    return Err(ErrorOrCtxJmp::Error(anyhow!(
-                        "Only instances have properties."
-                    )))
-                }
+            Expr::Lambda(params, annotations, body) => Object::Function(
+                ast::FuncObject::new_lambda(params.clone(), body.clone(), interpreter.env.clone())
+                    .with_annotations(annotations.clone()),
+            ),
+            Expr::Get(object, property) => {
+                return get_property(Evaluator::evaluate(object, env, interpreter)?, property)
+            }
+            Expr::GetSafe(object, property) => match Evaluator::evaluate(object, env, interpreter)?
+            {
+                Nil => Nil,
+                object => return get_property(object, property),
             },
             Expr::Set(object, property, value) => {
                 match Evaluator::evaluate(object, Rc::clone(&env), interpreter)? {
@@ -161,46 +165,143 @@ impl Evaluator {
                             .set(property.token.lexeme.clone(), value.clone());
                         value
                     }
-                    _ => return Err(ErrorOrCtxJmp::Error(anyhow!("Only instances have fields."))),
+                    _ => {
+                        return Err(ErrorOrCtxJmp::Error(anyhow!("Only instances have fields."))
+                            .at(property.token.span))
+                    }
                 }
             }
             Expr::Super(super_class, method) => {
-                let distance = interpreter.get_distance(super_class);
-                let super_class = match get_env(&env.borrow(), super_class, distance)?
-                    .borrow()
-                    .clone()
-                {
-                    Class(c) => c,
-                    _ => unreachable!(),
-                };
+                Evaluator::super_method(super_class, method, env, interpreter)?
+            }
+        };
+        Ok(r)
+    }
 
-                let object = match get_env(
-                    &env.borrow(),
-                    &Token::new(TokenType::This, Span::default()).into(),
-                    distance - 1,
-                )?
-                .borrow()
-                .clone()
-                {
-                    Instance(i) => i,
-                    _ => unreachable!(),
-                };
+    /// `super.method`. Fields live on the instance rather than on a class, so `super.x`
+    /// only ever looks up methods, like reference Lox; the result is bound to `this`.
+    /// Kept out of line like `interpolate`.
+    #[inline(never)]
+    fn super_method<W: Write, E: Write>(
+        super_class: &Identifier,
+        method: &Identifier,
+        env: Env,
+        interpreter: &mut Interpreter<W, E>,
+    ) -> EvalResult {
+        use Object::*;
+        let distance = interpreter.get_distance(super_class);
+        let super_class = match get_env(&env.borrow(), super_class, distance)?
+            .borrow()
+            .clone()
+        {
+            Class(c) => c,
+            _ => unreachable!(),
+        };
 
-                let super_class_method = match super_class.find_method(&method.token.lexeme as &str)
-                {
-                    Some(m) => m,
-                    None => {
-                        return Err(ErrorOrCtxJmp::Error(anyhow!(
-                            "Undefined property '{}'.",
-                            &method.token.lexeme
-                        )));
-                    }
-                };
+        let object = match get_env(
+            &env.borrow(),
+            &Token::new(TokenType::This, Span::default()).into(),
+            distance - 1,
+        )?
+        .borrow()
+        .clone()
+        {
+            Instance(i) => i,
+            _ => unreachable!(),
+        };
 
-                Object::Function(FuncObject::bind(super_class_method, object)?)
+        let super_class_method = match super_class.find_method(&method.token.lexeme as &str) {
+            Some(m) => m,
+            None => {
+                return Err(ErrorOrCtxJmp::Error(anyhow!(
+                    "Undefined property '{}'.",
+                    &method.token.lexeme
+                ))
+                .at(method.token.span));
             }
         };
-        Ok(r)
+
+        Ok(Object::Function(FuncObject::bind(
+            super_class_method,
+            object,
+        )?))
+    }
+
+    /// The value of `${expr}`, converted to a string as `str` would. Kept out of line
+    /// so `evaluate`'s frame stays small.
+    #[inline(never)]
+    fn interpolate<W: Write, E: Write>(
+        expr: &Expr,
+        env: Env,
+        interpreter: &mut Interpreter<W, E>,
+    ) -> EvalResult {
+        Ok(match Evaluator::evaluate(expr, env, interpreter)? {
+            Object::String(s) => Object::String(s),
+            o => Object::String(o.to_string()),
+        })
+    }
+}
+
+/// Fast path for the common `Int op Int` case, e.g. `i = i + 1` in loops, which skips
+/// moving both operands through the general match. Returns `None` for division by zero
+/// or overflow so the caller reports the error, as `fold` leaves those to the runtime.
+/// Integer division truncates toward zero, as in Rust, so `-7 / 2` is `-3` rather than
+/// `-4`.
+#[inline(always)]
+fn int_binary(bop: BinaryOp, a: i64, b: i64) -> Option<Object> {
+    use BinaryOp::*;
+    Some(match bop {
+        Add => Object::Int(a.checked_add(b)?),
+        Sub => Object::Int(a.checked_sub(b)?),
+        Mul => Object::Int(a.checked_mul(b)?),
+        Div => Object::Int(a.checked_div(b)?),
+        Lt => Object::Boolean(a < b),
+        Gt => Object::Boolean(a > b),
+        Le => Object::Boolean(a <= b),
+        Ge => Object::Boolean(a >= b),
+        Eq => Object::Boolean(a == b),
+        Ne => Object::Boolean(a != b),
+        Or | And => return None,
+    })
+}
+
+/// Builds the error out of line, keeping it off `Evaluator::evaluate`'s frame.
+#[cold]
+#[inline(never)]
+fn runtime_error(message: &'static str, span: Span) -> ErrorOrCtxJmp {
+    ErrorOrCtxJmp::Error(anyhow!(message)).at(span)
+}
+
+/// `object.property` on an instance or a module, kept off `Evaluator::evaluate`'s frame.
+#[inline(never)]
+fn get_property(object: Object, property: &Identifier) -> EvalResult {
+    let value = match object {
+        Object::Instance(i) => ClassInstance::get(&property.token.lexeme, i),
+        Object::Module(m) => m.get(&property.token.lexeme),
+        _ => {
+            return Err(runtime_error(
+                "Only instances have properties.",
+                property.token.span,
+            ))
+        }
+    };
+    value.map_err(|e| ErrorOrCtxJmp::from(e).at(property.token.span))
+}
+
+/// `+`, `-`, `*` or `/` on promoted operands, or the message of the error they raise.
+#[inline(never)]
+fn arithmetic(bop: BinaryOp, numbers: Numbers) -> std::result::Result<Object, &'static str> {
+    use BinaryOp::*;
+    match numbers {
+        Numbers::Ints(_, 0) if bop == Div => Err("Cannot divide by 0."),
+        Numbers::Floats(_, b) if bop == Div && b == 0.0 => Err("Cannot divide by 0."),
+        Numbers::Ints(a, b) => int_binary(bop, a, b).ok_or("Integer overflow."),
+        Numbers::Floats(a, b) => Ok(Object::Float(match bop {
+            Add => a + b,
+            Sub => a - b,
+            Mul => a * b,
+            _ => a / b,
+        })),
     }
 }
 
@@ -244,9 +345,122 @@ mod tests {
     test_eval_expr_ok!(not_bool, "!false", Object::Boolean(true));
     test_eval_expr_ok!(mul_neg_ints, "-20*-20", Object::Int(400));
 
+    test_eval_expr_ok!(int_div, "-7 / 2", Object::Int(-3));
+    test_eval_expr_ok!(int_div_neg_divisor, "7 / -2", Object::Int(-3));
+    test_eval_expr_ok!(int_div_both_neg, "-7 / -2", Object::Int(3));
+    test_eval_expr_ok!(int_minus_float, "1 - 0.5", Object::Float(0.5));
+    test_eval_expr_ok!(float_times_int, "0.5 * 4", Object::Float(2.0));
+    test_eval_expr_ok!(int_div_float, "1 / 4.0", Object::Float(0.25));
+    test_eval_expr_ok!(int_lt, "7 < 2", Object::Boolean(false));
+    test_eval_expr_ok!(int_ge, "3 >= 3", Object::Boolean(true));
+    test_eval_expr_ok!(int_ne, "3 != 3", Object::Boolean(false));
+
+    test_interpret_ok!(
+        int_loop,
+        "var i = 0; var sum = 0; while (i < 100) { sum = sum + i; i = i + 1; } print sum;",
+        "4950\n"
+    );
+    test_interpret_err!(int_divide_by_zero, "print 1 / 0;", "Cannot divide by 0.");
+    test_interpret_err!(
+        int_min_divided_by_minus_one,
+        "var min = -9223372036854775807 - 1; var d = -1; print min / d;",
+        "Integer overflow."
+    );
+    test_interpret_err!(
+        int_add_overflow,
+        "var max = 9223372036854775807; print max + 1;",
+        "Integer overflow."
+    );
+    test_interpret_err!(
+        int_mul_overflow,
+        "var big = 4294967296; print big * big;",
+        "Integer overflow."
+    );
+    test_interpret_err!(
+        int_sub_overflow,
+        "var min = -9223372036854775807 - 1; print min - 1;",
+        "Integer overflow."
+    );
+    test_interpret_err!(
+        int_negate_overflow,
+        "var min = -9223372036854775807 - 1; print -min;",
+        "Integer overflow."
+    );
+    test_interpret_err!(
+        folded_overflow_fails_at_runtime,
+        "print 9223372036854775807 + 1;",
+        "Integer overflow."
+    );
+    test_interpret_err!(
+        int_divide_by_float_zero,
+        "print 1 / 0.0;",
+        "Cannot divide by 0."
+    );
+
+    test_eval_expr_ok!(int_eq_float, "1 == 1.0", Object::Boolean(true));
+    test_eval_expr_ok!(int_ne_float, "2 != 2.0", Object::Boolean(false));
+    test_eval_expr_ok!(int_eq_string, r#"1 == "1""#, Object::Boolean(false));
+
     test_eval_expr_ok!(
         add_strs,
         r#" "con"+ "catenate""#,
         Object::String("concatenate".into())
     );
+
+    #[test]
+    fn runtime_error_span() {
+        let (result, _) =
+            test_interpret!("var a = 1;\nprint a -  \"b\";", |interpreter| interpreter);
+        let err = result.expect_err("expected an error");
+        assert_eq!(err.to_string(), "Operands must be numbers.");
+        assert_eq!(err.span(), Some(Span::new(2, 9)));
+    }
+
+    #[test]
+    fn call_error_span() {
+        let (result, _) = test_interpret!("var a = 1;\n\n  a();", |interpreter| interpreter);
+        let err = result.expect_err("expected an error");
+        assert_eq!(err.span(), Some(Span::new(3, 4)));
+    }
+
+    #[test]
+    fn property_error_span() {
+        for input in ["class C {}\nprint C().y;", "class C {}\nprint C()?.y;"] {
+            let (result, _) = test_interpret!(input, |interpreter| interpreter);
+            let err = result.expect_err("expected an error");
+            assert_eq!(err.to_string(), "Undefined property 'y'.");
+            assert_eq!(err.span().map(|span| span.line), Some(2));
+        }
+    }
+
+    test_interpret_err!(
+        call_string,
+        r#""str"();"#,
+        "Can only call functions and classes."
+    );
+    test_interpret_err!(
+        call_instance,
+        "class A {} A()();",
+        "Can only call functions and classes."
+    );
+
+    test_interpret_ok!(
+        optional_chaining,
+        r#"
+        class A {}
+        var a = A();
+        a.field = "set";
+        var n = nil;
+        print n?.field;
+        print a?.field;
+        print a?.field == a.field;
+        "#,
+        "nil\n\"set\"\ntrue\n"
+    );
+
+    test_interpret_err!(
+        optional_chaining_on_number,
+        "print 1?.field;",
+        "Only instances have properties."
+    );
 }
@@ -1,4 +1,12 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs::read_to_string;
+use std::io::stderr;
+use std::io::Stderr;
 use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
 use std::rc::Rc;
 
 use lexer::Span;
@@ -7,35 +15,341 @@ use lexer::TokenType;
 
 use crate::anyhow;
 use crate::ast::*;
+use crate::callable::Callable;
+use crate::native::expect_filesystem_access;
+use crate::native::XorShift;
+use crate::native::NATIVES;
 use crate::ErrorOrCtxJmp;
 use crate::Evaluator;
+use crate::Resolver;
 use crate::Result;
 
+/// What natives may do outside of the interpreter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Permissions {
+    pub filesystem: bool,
+    pub stdin: bool,
+    pub exit: bool,
+}
+
+impl Permissions {
+    pub fn all() -> Self {
+        Self {
+            filesystem: true,
+            stdin: true,
+            exit: true,
+        }
+    }
+
+    pub fn none() -> Self {
+        Self {
+            filesystem: false,
+            stdin: false,
+            exit: false,
+        }
+    }
+}
+
+impl Default for Permissions {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+/// A program resolved once by `Interpreter::prepare`, to be run any number of times with
+/// `Interpreter::run_prepared`.
+#[derive(Debug, Clone)]
+pub struct PreparedProgram {
+    stmts: Vec<Stmt>,
+    locals: Vec<usize>,
+}
+
+/// Called with every value a `print` statement outputs, see `Interpreter::set_print_hook`.
+struct PrintHook(Box<dyn FnMut(&Object)>);
+
+impl std::fmt::Debug for PrintHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PrintHook")
+    }
+}
+
 #[derive(Debug)]
-pub struct Interpreter<W> {
+pub struct Interpreter<W, E = Stderr> {
     pub(crate) writer: W,
+    pub(crate) error_writer: E,
     pub(crate) env: Env,
+    globals: Env,
+    /// Names of the natives, constants and registered functions, which modules see too.
+    builtins: Vec<String>,
     envs: Vec<Env>,
     pub(crate) locals: Vec<usize>,
+    truthiness: Truthiness,
+    raw_print: bool,
+    float_precision: Option<usize>,
+    pub(crate) check_param_types: bool,
+    pub(crate) permissions: Permissions,
+    pub(crate) fold_constants: bool,
+    /// Body and closure of the running function, which identify it for tail calls.
+    pub(crate) current_function: Option<(Rc<Vec<Stmt>>, Env)>,
+    print_hook: Option<PrintHook>,
+    /// Directory `import` paths are relative to, that of the file being run.
+    script_dir: PathBuf,
+    imported: HashSet<PathBuf>,
+    /// Files the resolver parsed for an `import` that has yet to run, by canonical path.
+    parsed_imports: HashMap<PathBuf, Vec<Stmt>>,
+    /// Files whose import is running, innermost last.
+    importing: Vec<PathBuf>,
+    trace: bool,
+    steps: usize,
+    step_limit: Option<usize>,
+    pub(crate) rng: XorShift,
 }
 
 impl<W: Write> Interpreter<W> {
+    /// Program output goes to `writer`, reported errors to stderr.
     #[inline(always)]
     pub fn new(writer: W) -> Self {
+        let globals = new_env();
+        let mut builtins = Vec::new();
+        for native in NATIVES {
+            globals.borrow_mut().init_variable(
+                Token::new_with_lexeme(TokenType::Ident, native.name, Span::default()).into(),
+                Object::NativeFunction(*native),
+            );
+            builtins.push(native.name.to_string());
+        }
+        globals.borrow_mut().init_variable(
+            Token::new_with_lexeme(TokenType::Ident, "PI", Span::default()).into(),
+            Object::Float(std::f64::consts::PI),
+        );
+        builtins.push("PI".to_string());
+
         Self {
             writer,
-            env: new_env(),
+            error_writer: stderr(),
+            env: Rc::clone(&globals),
+            globals,
+            builtins,
             envs: Vec::new(),
             locals: vec![usize::MAX],
+            truthiness: Truthiness::Lox,
+            raw_print: false,
+            float_precision: None,
+            check_param_types: false,
+            permissions: Permissions::all(),
+            fold_constants: false,
+            current_function: None,
+            print_hook: None,
+            script_dir: PathBuf::new(),
+            imported: HashSet::new(),
+            parsed_imports: HashMap::new(),
+            importing: Vec::new(),
+            trace: false,
+            steps: 0,
+            step_limit: None,
+            rng: XorShift::from_time(),
+        }
+    }
+
+    /// Like `new`, but natives may not touch the filesystem, stdin or the process.
+    pub fn sandboxed(writer: W) -> Self {
+        Self::new(writer).with_permissions(Permissions::none())
+    }
+}
+
+impl<W: Write, E: Write> Interpreter<W, E> {
+    /// Sends errors passed to `report_error` to `error_writer` instead of stderr.
+    pub fn with_error_writer<E2: Write>(self, error_writer: E2) -> Interpreter<W, E2> {
+        Interpreter {
+            writer: self.writer,
+            error_writer,
+            env: self.env,
+            globals: self.globals,
+            builtins: self.builtins,
+            envs: self.envs,
+            locals: self.locals,
+            truthiness: self.truthiness,
+            raw_print: self.raw_print,
+            float_precision: self.float_precision,
+            check_param_types: self.check_param_types,
+            permissions: self.permissions,
+            fold_constants: self.fold_constants,
+            current_function: self.current_function,
+            print_hook: self.print_hook,
+            script_dir: self.script_dir,
+            imported: self.imported,
+            parsed_imports: self.parsed_imports,
+            importing: self.importing,
+            trace: self.trace,
+            steps: self.steps,
+            step_limit: self.step_limit,
+            rng: self.rng,
         }
     }
 
+    /// Defines the global `name` before running a program, e.g. to pass configuration in.
+    pub fn define_global(&mut self, name: &str, value: Object) {
+        self.globals.borrow_mut().init_variable(
+            Token::new_with_lexeme(TokenType::Ident, name, Span::default()).into(),
+            value,
+        );
+    }
+
+    /// Exposes a Rust function to scripts as the global `name`.
+    pub fn register_native(
+        &mut self,
+        name: &str,
+        arity: usize,
+        f: Rc<dyn Fn(Vec<Object>) -> anyhow::Result<Object>>,
+    ) {
+        self.define_global(
+            name,
+            Object::HostFunction(HostFunction {
+                name: name.into(),
+                arity,
+                func: f,
+            }),
+        );
+        self.builtins.push(name.into());
+    }
+
+    /// Calls `hook` with each printed value before it is written out.
+    pub fn set_print_hook(&mut self, hook: Box<dyn FnMut(&Object)>) {
+        self.print_hook = Some(PrintHook(hook));
+    }
+
+    /// Writes `e` to the error writer as one `[line N] Error...` report, followed by the
+    /// offending source line when the parser kept it.
+    pub fn report_error(&mut self, e: &ErrorOrCtxJmp) {
+        let _ = writeln!(self.error_writer, "{}", e.line_report());
+        if let Some(snippet) = e.snippet() {
+            let _ = writeln!(self.error_writer, "{}", snippet);
+        }
+    }
+
+    /// Additionally treat `0`, `0.0` and `""` as falsey in conditions and logical operators.
+    #[inline(always)]
+    pub fn with_c_like_truthiness(mut self, enabled: bool) -> Self {
+        self.truthiness = match enabled {
+            true => Truthiness::CLike,
+            false => Truthiness::Lox,
+        };
+        self
+    }
+
+    /// Make `print` write strings without surrounding quotes, like reference Lox.
+    #[inline(always)]
+    pub fn with_raw_print(mut self, enabled: bool) -> Self {
+        self.raw_print = enabled;
+        self
+    }
+
+    /// The file being run, which `import` paths are resolved against. Without it they
+    /// are relative to the working directory.
+    pub fn with_script_path(mut self, path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        self.script_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        self.imported.insert(canonical(path));
+        self
+    }
+
+    #[inline(always)]
+    pub fn with_permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    /// Fold literal arithmetic such as `1 + 2 * 3` before running a program.
+    #[inline(always)]
+    pub fn with_constant_folding(mut self, enabled: bool) -> Self {
+        self.fold_constants = enabled;
+        self
+    }
+
+    /// Make `print` write floats with exactly `precision` decimal places.
+    #[inline(always)]
+    pub fn with_float_precision(mut self, precision: usize) -> Self {
+        self.float_precision = Some(precision);
+        self
+    }
+
+    /// Check arguments against parameter annotations such as `fun f(a: int)` on each call.
+    #[inline(always)]
+    pub fn with_param_type_checks(mut self, enabled: bool) -> Self {
+        self.check_param_types = enabled;
+        self
+    }
+
+    /// Fails once more than `limit` statements and expressions have been evaluated.
+    #[inline(always)]
+    pub fn with_step_limit(mut self, limit: usize) -> Self {
+        self.step_limit = Some(limit);
+        self
+    }
+
+    /// Seeds `random` and `random_int` so a script produces the same numbers on every run.
+    #[inline(always)]
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = XorShift::new(seed);
+        self
+    }
+
+    /// Write a line naming each statement and its source line to the error writer
+    /// before running it.
+    #[inline(always)]
+    pub fn with_trace(mut self, enabled: bool) -> Self {
+        self.trace = enabled;
+        self
+    }
+
+    fn trace_stmt(&mut self, stmt: &Stmt) {
+        let _ = match stmt.span() {
+            Some(span) => writeln!(self.error_writer, "[line {}] {}", span.line, stmt.kind()),
+            None => writeln!(self.error_writer, "{}", stmt.kind()),
+        };
+    }
+
+    /// Counts an expression against the step limit, which is checked at the next
+    /// statement.
+    #[inline(always)]
+    pub(crate) fn count_step(&mut self) {
+        self.steps += 1;
+    }
+
+    /// Counts a statement against the step limit; `span` locates the error and is only
+    /// asked for once the limit is hit.
+    #[inline(always)]
+    pub(crate) fn step(&mut self, span: impl FnOnce() -> Option<Span>) -> Result<()> {
+        self.steps += 1;
+        match self.step_limit {
+            Some(limit) if self.steps > limit => Err(step_limit_exceeded(span)),
+            _ => Ok(()),
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn is_truth(&self, o: &Object) -> bool {
+        is_truthy(o, self.truthiness)
+    }
+
     #[inline(always)]
     fn run(&mut self, stmt: &Stmt) -> Result<()> {
+        self.step(|| stmt.span())?;
+        if self.trace {
+            self.trace_stmt(stmt);
+        }
         match stmt {
             Stmt::Print(expr) => {
                 let o = Evaluator::evaluate(expr, Rc::clone(&self.env), self)?;
-                let res = writeln!(self.writer, "{}", o);
+                if let Some(PrintHook(hook)) = self.print_hook.as_mut() {
+                    hook(&o);
+                }
+                let res = match (&o, self.float_precision) {
+                    (Object::Float(f), Some(precision)) => {
+                        writeln!(self.writer, "{:.*}", precision, f)
+                    }
+                    _ => writeln!(self.writer, "{}", o.render(!self.raw_print)),
+                };
                 if res.is_err() {
                     return Err(ErrorOrCtxJmp::Error(anyhow!("unable to write")));
                 }
@@ -60,7 +374,7 @@ impl<W: Write> Interpreter<W> {
                 else_branch,
             }) => {
                 let cond = Evaluator::evaluate(cond, Rc::clone(&self.env), self)?;
-                match cond.is_truth() {
+                match self.is_truth(&cond) {
                     true => {
                         self.run(if_branch)?;
                     }
@@ -71,96 +385,311 @@ impl<W: Write> Interpreter<W> {
                     }
                 };
             }
-            Stmt::Loop(Loop { cond, body }) => loop {
+            Stmt::Loop(Loop { cond, body, update }) => loop {
                 let cond_val = Evaluator::evaluate(cond, Rc::clone(&self.env), self)?;
-                if !cond_val.is_truth() {
+                if !self.is_truth(&cond_val) {
                     break;
                 }
                 let res = self.run(body);
                 match res {
-                    Ok(_) => {}
+                    Ok(_) | Err(ErrorOrCtxJmp::ContJump) => {}
                     Err(ErrorOrCtxJmp::BrkJump) => {
                         break;
                     }
                     e => e?,
                 }
+                if let Some(update) = update {
+                    Evaluator::evaluate(update, Rc::clone(&self.env), self)?;
+                }
             },
-            Stmt::FunctionDecl(FunctionDecl { name, params, body }) => {
-                let func = Object::Function(FuncObject::new(
-                    name.clone(),
-                    params.clone(),
-                    body.clone(),
-                    self.env.clone(),
-                    false,
-                ));
+            Stmt::ForEach(for_each) => self.run_for_each(for_each)?,
+            Stmt::FunctionDecl(FunctionDecl {
+                name,
+                params,
+                annotations,
+                body,
+            }) => {
+                let func = Object::Function(
+                    FuncObject::new(
+                        name.clone(),
+                        params.clone(),
+                        body.clone(),
+                        self.env.clone(),
+                        false,
+                    )
+                    .with_annotations(annotations.clone()),
+                );
 
                 self.env.borrow_mut().init_variable(name.clone(), func);
             }
-            Stmt::Return(value) => {
+            Stmt::Return(Expr::Call(callee, args, span), _) => {
+                return self.return_call(callee, args, *span);
+            }
+            Stmt::Return(value, _) => {
                 let value = Evaluator::evaluate(value, Rc::clone(&self.env), self)?;
                 return Err(ErrorOrCtxJmp::RetJump { object: value });
             }
-            Stmt::ClassDecl(ClassDecl {
-                name,
-                super_class,
-                methods,
-            }) => {
-                let (super_class, has_super_class) = if let Some(super_class) = super_class {
-                    let sc = Evaluator::evaluate(super_class, Rc::clone(&self.env), self)?;
-                    match sc {
-                        Object::Class(c) => (Some(Box::new(c)), true),
-                        _ => {
-                            return Err(ErrorOrCtxJmp::Error(anyhow!(
-                                "Superclass must be a class."
-                            )))
-                        }
-                    }
-                } else {
-                    (None, false)
-                };
+            Stmt::ClassDecl(class) => self.declare_class(class)?,
+            Stmt::Break(_) => {
+                return Err(ErrorOrCtxJmp::BrkJump);
+            }
+            Stmt::Continue(_) => {
+                return Err(ErrorOrCtxJmp::ContJump);
+            }
+            Stmt::Import(import) => self.import(import)?,
+        };
+        Ok(())
+    }
+
+    /// Binds `class` in the current scope, with `super` in a scope of its own around the
+    /// methods when there is a superclass.
+    #[inline(never)]
+    fn declare_class(&mut self, class: &ClassDecl) -> Result<()> {
+        let ClassDecl {
+            name,
+            super_class,
+            methods,
+        } = class;
+        let (super_class, has_super_class) = if let Some(super_class) = super_class {
+            let sc = Evaluator::evaluate(super_class, Rc::clone(&self.env), self)?;
+            match sc {
+                Object::Class(c) => (Some(c), true),
+                _ => return Err(ErrorOrCtxJmp::Error(anyhow!("Superclass must be a class."))),
+            }
+        } else {
+            (None, false)
+        };
+
+        if let Some(ref sc) = super_class {
+            self.push_scope();
+            self.env.borrow_mut().init_variable(
+                Token::new(TokenType::Super, Span::default()).into(),
+                Object::Class(Rc::clone(sc)),
+            );
+        }
+        let class = Object::Class(Rc::new(ClassObject::new(
+            name.clone(),
+            super_class,
+            methods
+                .iter()
+                .map(|method| {
+                    let name = method.name.clone();
+                    let is_initializer = &name.token.lexeme == "init";
+                    (
+                        name.token.lexeme.clone(),
+                        FuncObject::new(
+                            name,
+                            method.params.clone(),
+                            method.body.clone(),
+                            Rc::clone(&self.env),
+                            is_initializer,
+                        )
+                        .with_annotations(method.annotations.clone()),
+                    )
+                })
+                .collect(),
+        )));
 
-                if let Some(ref sc) = super_class {
-                    self.push_scope();
-                    let scc = *sc.clone();
-                    self.env.borrow_mut().init_variable(
-                        Token::new(TokenType::Super, Span::default()).into(),
-                        Object::Class(scc),
-                    );
+        if has_super_class {
+            self.pop_scope();
+        }
+        self.env.borrow_mut().init_variable(name.clone(), class);
+        Ok(())
+    }
+
+    /// Runs `for (name in iterable) body` over a range, or an instance through its
+    /// `iter()`, `has_next()` and `next()` methods.
+    #[inline(never)]
+    fn run_for_each(&mut self, for_each: &ForEach) -> Result<()> {
+        let ForEach {
+            name,
+            iterable,
+            body,
+        } = for_each;
+        match Evaluator::evaluate(iterable, Rc::clone(&self.env), self)? {
+            Object::Range { start, end } => {
+                for i in start..end {
+                    if !self.run_for_each_body(name, body, Object::Int(i))? {
+                        break;
+                    }
                 }
-                let class = Object::Class(ClassObject::new(
-                    name.clone(),
-                    super_class,
-                    methods
-                        .iter()
-                        .map(|method| {
-                            let name = method.name.clone();
-                            let is_initializer = &name.token.lexeme == "init";
-                            (
-                                name.token.lexeme.clone(),
-                                FuncObject::new(
-                                    name,
-                                    method.params.clone(),
-                                    method.body.clone(),
-                                    Rc::clone(&self.env),
-                                    is_initializer,
-                                ),
-                            )
-                        })
-                        .collect(),
-                ));
-
-                if has_super_class {
-                    self.pop_scope();
+            }
+            Object::Instance(instance) => {
+                let iter = match self.call_method(instance, "iter")? {
+                    Object::Instance(iter) => iter,
+                    o => {
+                        return Err(ErrorOrCtxJmp::Error(anyhow!(
+                            "iter() must return an instance, not {}.",
+                            o.type_name()
+                        ))
+                        .at(iterable.span().unwrap_or(name.token.span)))
+                    }
+                };
+                loop {
+                    let has_next = self.call_method(Rc::clone(&iter), "has_next")?;
+                    if !self.is_truth(&has_next) {
+                        break;
+                    }
+                    let value = self.call_method(Rc::clone(&iter), "next")?;
+                    if !self.run_for_each_body(name, body, value)? {
+                        break;
+                    }
                 }
-                self.env.borrow_mut().init_variable(name.clone(), class);
             }
-            Stmt::Break => {
-                return Err(ErrorOrCtxJmp::BrkJump);
+            o => {
+                return Err(ErrorOrCtxJmp::Error(anyhow!(
+                    "Can only iterate over ranges and instances, not {}.",
+                    o.type_name()
+                ))
+                .at(iterable.span().unwrap_or(name.token.span)))
             }
-        };
+        }
         Ok(())
     }
 
+    /// Reads and parses the file `import "path";` refers to, or takes it from
+    /// `parsed_imports` when the resolver already did.
+    fn load_import(&mut self, path: &str) -> Result<(PathBuf, Vec<Stmt>)> {
+        let path = self.script_dir.join(path);
+        if let Some(stmts) = self.parsed_imports.remove(&canonical(&path)) {
+            return Ok((path, stmts));
+        }
+        expect_filesystem_access(self)?;
+        let source = read_to_string(&path).map_err(|e| {
+            ErrorOrCtxJmp::Error(anyhow!("Unable to import '{}': {}.", path.display(), e))
+        })?;
+        Ok((path, crate::parse_source(&source)?))
+    }
+
+    /// Parses the file `import "path";` refers to for the resolver, keeping it for the
+    /// import to run later.
+    pub(crate) fn parse_import(&mut self, path: &str) -> Result<&[Stmt]> {
+        let (path, stmts) = self.load_import(path)?;
+        Ok(self.parsed_imports.entry(canonical(&path)).or_insert(stmts))
+    }
+
+    /// Runs an imported file in the global environment, or as a module when it is
+    /// imported `as` a name. Files already imported into the global environment, or the
+    /// file being run, are skipped there, which also stops import cycles.
+    fn import(&mut self, import: &Import) -> Result<()> {
+        let path = match &import.alias {
+            Some(alias) => {
+                let module = self.import_module(&import.path, alias)?;
+                self.env
+                    .borrow_mut()
+                    .init_variable(alias.clone(), Object::Module(module));
+                return Ok(());
+            }
+            None => &import.path,
+        };
+        if !self.imported.insert(canonical(&self.script_dir.join(path))) {
+            return Ok(());
+        }
+        let (path, stmts) = self.load_import(path)?;
+        self.run_import(&path, stmts, Rc::clone(&self.globals))
+    }
+
+    /// Runs an imported file in an environment of its own, which only starts out with the
+    /// natives, and returns its top-level names as a module.
+    fn import_module(&mut self, path: &str, alias: &Identifier) -> Result<Module> {
+        let (path, stmts) = self.load_import(path)?;
+        let env = new_env();
+        for name in &self.builtins {
+            if let Some(value) = self.globals.borrow().lookup(name) {
+                env.borrow_mut().init_variable(
+                    Token::new_with_lexeme(TokenType::Ident, name, Span::default()).into(),
+                    value.borrow().clone(),
+                );
+            }
+        }
+        self.run_import(&path, stmts, Rc::clone(&env))?;
+        Ok(Module {
+            name: alias.token.lexeme.clone(),
+            env,
+        })
+    }
+
+    fn run_import(&mut self, path: &Path, mut stmts: Vec<Stmt>, env: Env) -> Result<()> {
+        let key = canonical(path);
+        if self.importing.contains(&key) {
+            return Err(ErrorOrCtxJmp::Error(anyhow!(
+                "Circular import of '{}'.",
+                path.display()
+            )));
+        }
+        if self.fold_constants {
+            fold_constants(&mut stmts);
+        }
+
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let script_dir = std::mem::replace(&mut self.script_dir, dir);
+        self.importing.push(key);
+        self.save_env(env);
+        let res = Resolver::new()
+            .resolve(&mut stmts, self)
+            .and_then(|_| self.run_many(&stmts));
+        self.reset_env();
+        self.importing.pop();
+        self.script_dir = script_dir;
+        res
+    }
+
+    /// Runs one iteration of a for-each loop with `name` bound to `value`. Returns `false`
+    /// once the body breaks out of the loop.
+    fn run_for_each_body(&mut self, name: &Identifier, body: &Stmt, value: Object) -> Result<bool> {
+        self.push_scope();
+        self.env.borrow_mut().init_variable(name.clone(), value);
+        let res = self.run(body);
+        self.pop_scope();
+        match res {
+            Ok(_) | Err(ErrorOrCtxJmp::ContJump) => Ok(true),
+            Err(ErrorOrCtxJmp::BrkJump) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Calls the method `name` of `instance` without arguments.
+    fn call_method(&mut self, instance: Rc<RefCell<ClassInstance>>, name: &str) -> Result<Object> {
+        ClassInstance::get(name, instance)?.call(vec![], self)
+    }
+
+    /// Looks `name` up from the current scope outwards, ignoring resolution. Meant for the
+    /// REPL and debugging, where there is no resolved use site to go through.
+    pub fn by_name(&self, name: &str) -> Option<Object> {
+        self.env.borrow().lookup(name).map(|o| o.borrow().clone())
+    }
+
+    /// `return f(...)` jumps back to the start of the current function when `f` is that
+    /// same function, instead of growing the call stack.
+    #[inline(never)]
+    fn return_call(&mut self, callee: &Expr, args: &[Argument], span: Span) -> Result<()> {
+        self.step(|| Some(span))?;
+        let args = args
+            .iter()
+            .map(|arg| Evaluator::evaluate(&arg.value, Rc::clone(&self.env), self))
+            .collect::<Result<Vec<_>>>()?;
+        let callee = Evaluator::evaluate(callee, Rc::clone(&self.env), self)?;
+        match (&callee, &self.current_function) {
+            (Object::Function(f), Some((body, closure)))
+                if Rc::ptr_eq(&f.body, body)
+                    && Rc::ptr_eq(&f.closure, closure)
+                    && !f.is_initializer
+                    && args.len() == f.params.len() =>
+            {
+                Err(ErrorOrCtxJmp::TailCall { args })
+            }
+            _ => {
+                let value = callee.call(args, self).map_err(|e| e.at(span))?;
+                Err(ErrorOrCtxJmp::RetJump { object: value })
+            }
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn is_global(&self, name: &str) -> bool {
+        self.globals.borrow().contains(name)
+    }
+
     #[inline(always)]
     pub fn get_distance(&self, id: &Identifier) -> usize {
         unsafe { *self.locals.get_unchecked(id.rid) }
@@ -172,6 +701,26 @@ impl<W: Write> Interpreter<W> {
         self.locals.push(distance);
     }
 
+    /// Resolves `stmts` once so they can be run repeatedly, by this or another
+    /// interpreter, without going through the resolver again.
+    pub fn prepare(&mut self, mut stmts: Vec<Stmt>) -> Result<PreparedProgram> {
+        if self.fold_constants {
+            fold_constants(&mut stmts);
+        }
+        Resolver::new().resolve(&mut stmts, self)?;
+        Ok(PreparedProgram {
+            stmts,
+            locals: self.locals.clone(),
+        })
+    }
+
+    /// Runs a program from `prepare`. The interpreter takes over the resolved variable
+    /// distances of the program, replacing any it had itself.
+    pub fn run_prepared(&mut self, program: &PreparedProgram) -> Result<()> {
+        self.locals.clone_from(&program.locals);
+        self.run_many(&program.stmts)
+    }
+
     #[inline(always)]
     pub fn run_many(&mut self, stmts: &[Stmt]) -> Result<()> {
         for stmt in stmts {
@@ -199,52 +748,38 @@ impl<W: Write> Interpreter<W> {
         self.env = push_env(Rc::clone(&self.env));
     }
 
+    /// Enters the scope of a call, with `args` bound to `params`.
+    #[inline(always)]
+    pub(crate) fn push_params_scope(&mut self, params: Rc<Vec<Identifier>>, args: Vec<Object>) {
+        self.env = push_params_env(Rc::clone(&self.env), params, args);
+    }
+
     #[inline(always)]
     pub(crate) fn pop_scope(&mut self) {
         self.env = pop_env(Rc::clone(&self.env));
     }
 }
 
+/// Builds the error out of line, keeping it off the frames of `run` and `evaluate`.
+#[cold]
+#[inline(never)]
+fn step_limit_exceeded(span: impl FnOnce() -> Option<Span>) -> ErrorOrCtxJmp {
+    let error = ErrorOrCtxJmp::Error(anyhow!("Execution step limit exceeded."));
+    match span() {
+        Some(span) => error.at(span),
+        None => error,
+    }
+}
+
+/// `path` with symlinks and `..` resolved, so one file has one key however it is named.
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
 #[cfg(test)]
 mod tests {
-
     use super::*;
 
-    use crate::lexer::Lexer;
-    use crate::lexer::Token;
-    use crate::parser::Parser;
-    use crate::test_utils::TestWriter;
-    use crate::Resolver;
-
-    #[allow(unused_macros)]
-    macro_rules! test_interpret_ok {
-        ($name: ident,$input: literal,$tt: expr) => {
-            #[test]
-            fn $name() {
-                let fake_stdout = TestWriter::new();
-                {
-                    let input = $input;
-                    let lexer = Lexer::new(input.chars()).unwrap();
-                    let tokens: std::result::Result<Vec<Token>, _> = lexer.into_iter().collect();
-                    let tokens = tokens.expect("lexing error");
-                    let mut stmts = Parser::new(tokens.into_iter())
-                        .program()
-                        .expect("parsing error");
-
-                    dbg!($input);
-                    let mut interpreter = Interpreter::new(fake_stdout.clone());
-                    let mut resolver = Resolver::new();
-                    resolver
-                        .resolve(&mut stmts, &mut interpreter)
-                        .expect("variable resolution error");
-
-                    interpreter.run_many(&stmts).expect("interpret error");
-                }
-                assert_eq!(&fake_stdout.into_string(), $tt);
-            }
-        };
-    }
-
     test_interpret_ok!(print_string, r#" print "one"; "#, "\"one\"\n");
     test_interpret_ok!(
         print_multiple,
@@ -309,13 +844,73 @@ mod tests {
         r#"var a= 10; var b=false; if( a and b ) {print true;} else {print false;}"#,
         "false\n"
     );
-    /*
     test_interpret_ok!(
         while_stmt,
-        r#" var i=1; var sum=0; while (i<10) {i=i+1; sum=sum+i;} print sum;"#,
+        r#" var i=0; var sum=0; while (i<10) {sum=sum+i; i=i+1;} print sum;"#,
         "45\n"
     );
-    */
+    test_interpret_ok!(
+        while_increment_first,
+        r#" var i=1; var sum=0; while (i<10) {i=i+1; sum=sum+i;} print sum;"#,
+        "54\n"
+    );
+    test_interpret_ok!(
+        while_early_break,
+        r#"
+        var i=0;
+        while (true) {
+            if (i == 3) break;
+            print i;
+            i=i+1;
+        }
+        print i;
+        "#,
+        "0\n1\n2\n3\n"
+    );
+    test_interpret_ok!(
+        for_continue_runs_update,
+        r#"
+        for (var i = 0; i < 6; i = i + 1) {
+            if (i == 1 or i == 4) continue;
+            print i;
+        }
+        "#,
+        "0\n2\n3\n5\n"
+    );
+    test_interpret_ok!(
+        while_continue,
+        r#"
+        var i = 0;
+        while (i < 4) {
+            i = i + 1;
+            if (i == 2) continue;
+            print i;
+        }
+        for (var x in range(0, 3)) {
+            if (x == 1) continue;
+            print x;
+        }
+        "#,
+        "1\n3\n4\n0\n2\n"
+    );
+    test_interpret_ok!(
+        while_nested,
+        r#"
+        var i=0;
+        var pairs=0;
+        while (i<4) {
+            var j=0;
+            while (j<i) {
+                if (j == 2) break;
+                pairs=pairs+1;
+                j=j+1;
+            }
+            i=i+1;
+        }
+        print pairs;
+        "#,
+        "5\n"
+    );
     test_interpret_ok!(
         variable_add,
         r#" var i=100; { i=i+111; } print i;"#,
@@ -556,6 +1151,38 @@ mod tests {
         "<instance@Egotist>\n"
     );
 
+    test_interpret_ok!(
+        bound_method_keeps_this,
+        r#"
+        class Named {
+            init(name) { this.name = name; }
+            say() { return this.name; }
+        }
+        class Holder {}
+
+        fun call(f) { return f(); }
+        print call(Named("argument").say);
+
+        fun pick() {
+            var n = Named("returned");
+            return n.say;
+        }
+        print pick()();
+
+        var holder = Holder();
+        holder.f = Named("field").say;
+        holder.name = "holder";
+        print holder.f();
+
+        class Loud < Named {
+            say() { return super.say() + "!"; }
+        }
+        var loud = Loud("super").say;
+        print call(loud);
+        "#,
+        "\"argument\"\n\"returned\"\n\"field\"\n\"super!\"\n"
+    );
+
     test_interpret_ok!(
         instance_this,
         r#"
@@ -589,6 +1216,27 @@ mod tests {
         "<instance@Thing>\n"
     );
 
+    test_interpret_ok!(
+        this_in_lambda,
+        r#"
+        class Counter {
+            init() { this.count = 0; }
+            incrementer() {
+                return fun () {
+                    this.count = this.count + 1;
+                    return this.count;
+                };
+            }
+        }
+        var counter = Counter();
+        var inc = counter.incrementer();
+        inc();
+        print inc();
+        print counter.count;
+        "#,
+        "2\n2\n"
+    );
+
     test_interpret_ok!(
         multiple_this,
         r#"
@@ -717,4 +1365,669 @@ print a;
         "#,
         "6\n8\n12\n10\n15\n20\n40\n12\n18\n24\n30\n14\n21\n28\n35\n42\n16\n24\n32\n40\n48\n56\n18\n27\n36\n45\n54\n63\n72\n"
     );
+
+    test_interpret_ok!(
+        reference_truthiness,
+        r#"
+        if (0) print "zero"; else print "no zero";
+        if ("") print "empty"; else print "no empty";
+        print !0.0;
+        print nil or false;
+        "#,
+        "\"zero\"\n\"empty\"\nfalse\nfalse\n"
+    );
+
+    test_interpret_ok!(
+        c_like_truthiness,
+        r#"
+        if (0) print "zero"; else print "no zero";
+        if ("") print "empty"; else print "no empty";
+        print !0.0;
+        print 0 or "fallback";
+        while (1) { print 1; break; }
+        "#,
+        "\"no zero\"\n\"no empty\"\ntrue\n\"fallback\"\n1\n",
+        |interpreter| interpreter.with_c_like_truthiness(true)
+    );
+
+    /// Runs `input` and returns the names captured by the function in the global `name`.
+    fn captured_names_of(input: &str, name: &str) -> Vec<String> {
+        let tokens: std::result::Result<Vec<_>, _> =
+            crate::lexer::Lexer::new(input.chars()).unwrap().collect();
+        let mut stmts = crate::parser::Parser::new(tokens.unwrap().into_iter())
+            .program()
+            .unwrap();
+        let mut interpreter = Interpreter::new(crate::test_utils::TestWriter::new());
+        crate::Resolver::new()
+            .resolve(&mut stmts, &mut interpreter)
+            .and_then(|_| interpreter.run_many(&stmts))
+            .unwrap();
+
+        let id = Token::new_with_lexeme(TokenType::Ident, name, Span::default()).into();
+        let function = get_env(&interpreter.globals.borrow(), &id, 0)
+            .unwrap()
+            .borrow()
+            .clone();
+        match function {
+            Object::Function(f) => f.captured_names(),
+            o => panic!("expected a function, got {}", o),
+        }
+    }
+
+    #[test]
+    fn closure_captured_names() {
+        let names = captured_names_of(
+            r#"
+            fun makeCounter() {
+              var i = 0;
+              fun count() {
+                i = i + 1;
+                print i;
+              }
+              return count;
+            }
+            var counter = makeCounter();
+            "#,
+            "counter",
+        );
+        assert_eq!(names[0], "count");
+        assert_eq!(names[1], "i");
+        assert!(names.contains(&"makeCounter".to_string()));
+    }
+
+    #[test]
+    fn closure_captures_parameter() {
+        let names = captured_names_of(
+            r#"
+            fun makeAdder(n, m) {
+              fun add(x) { return x + n + m; }
+              return add;
+            }
+            var add = makeAdder(1, 2);
+            "#,
+            "add",
+        );
+        assert_eq!(names[..3], ["add", "m", "n"]);
+        assert!(names.contains(&"makeAdder".to_string()));
+    }
+
+    test_interpret_ok!(quoted_print, r#" print "hi"; print 1; "#, "\"hi\"\n1\n");
+
+    test_interpret_ok!(
+        raw_print,
+        r#" print "hi"; print 1; print nil; "#,
+        "hi\n1\nnil\n",
+        |interpreter| interpreter.with_raw_print(true)
+    );
+
+    test_interpret_err!(
+        step_limit_stops_infinite_loop,
+        "while (true) {}",
+        "Execution step limit exceeded.",
+        |interpreter| interpreter.with_step_limit(1000)
+    );
+
+    test_interpret_ok!(
+        step_limit_allows_short_programs,
+        "var i = 0; while (i < 3) i = i + 1; print i;",
+        "3\n",
+        |interpreter| interpreter.with_step_limit(1000)
+    );
+
+    #[test]
+    fn step_limit_error_line() {
+        let (result, _) = test_interpret!("var i = 0;\nwhile (i >= 0) i = i + 1;", |interpreter| {
+            interpreter.with_step_limit(1000)
+        });
+        let err = result.expect_err("expected an error");
+        assert_eq!(err.to_string(), "Execution step limit exceeded.");
+        assert_eq!(err.span().map(|span| span.line), Some(2));
+    }
+
+    #[test]
+    fn for_each_error_line() {
+        let (result, _) = test_interpret!("\nfor (var x in 1) print x;", |interpreter| interpreter);
+        let err = result.expect_err("expected an error");
+        assert_eq!(err.span().map(|span| span.line), Some(2));
+    }
+
+    test_interpret_ok!(
+        init_bare_return,
+        r#"
+        class A {
+          init(early) {
+            this.x = 1;
+            if (early) return;
+            this.x = 2;
+          }
+        }
+        print A(true).x;
+        print A(false).x;
+        "#,
+        "1\n2\n"
+    );
+
+    test_interpret_err!(
+        init_return_value,
+        "class A { init() { return 5; } }",
+        "Error at 'return': Can't return a value from an initializer."
+    );
+
+    test_interpret_ok!(
+        init_nested_function_return_value,
+        r#"
+        class A {
+          init() {
+            fun f() { return 5; }
+            this.x = f();
+          }
+        }
+        print A().x;
+        "#,
+        "5\n"
+    );
+
+    test_interpret_ok!(
+        bound_method_keeps_instance_alive,
+        r#"
+        class Egotist {
+          init(name) { this.name = name; }
+          speak() { print this.name; }
+        }
+        var m = Egotist("jane").speak;
+        m();
+        fun detach() {
+          var e = Egotist("joe");
+          return e.speak;
+        }
+        var n = detach();
+        n();
+        m();
+        "#,
+        "\"jane\"\n\"joe\"\n\"jane\"\n"
+    );
+
+    #[test]
+    fn bound_method_holds_strong_reference() {
+        let class = ClassObject::new(
+            Token::new_with_lexeme(TokenType::Ident, "A", Span::default()).into(),
+            None,
+            vec![],
+        );
+        let instance = Rc::new(std::cell::RefCell::new(ClassInstance::new(class, vec![])));
+        let method = FuncObject::new_lambda(vec![], vec![], new_env());
+        let bound = FuncObject::bind(method, Rc::clone(&instance)).unwrap();
+        assert_eq!(Rc::strong_count(&instance), 2);
+        drop(instance);
+        let this = Token::new(TokenType::This, Span::default()).into();
+        let this = get_env(&bound.closure.borrow(), &this, 0).unwrap();
+        assert!(matches!(*this.borrow(), Object::Instance(_)));
+    }
+
+    test_interpret_ok!(
+        function_equality_is_identity,
+        r#"
+        fun make() { fun f() {} return f; }
+        fun g() {}
+        var h = g;
+        print g == h;
+        print make() == make();
+        "#,
+        "true\nfalse\n"
+    );
+
+    test_interpret_ok!(
+        class_equality_is_identity,
+        r#"
+        fun make() { class A {} return A; }
+        class B {}
+        var C = B;
+        print B == C;
+        print make() == make();
+        print B == B();
+        print B == "B";
+        "#,
+        "true\nfalse\nfalse\nfalse\n"
+    );
+
+    test_interpret_ok!(
+        method_equality_is_identity,
+        r#"
+        class A { f() {} g() {} }
+        var a = A();
+        var f = a.f;
+        print f == f;
+        print a.f == a.f;
+        print a.f == a.g;
+        print A().f == a.f;
+        print A == a.f;
+        "#,
+        "true\nfalse\nfalse\nfalse\nfalse\n"
+    );
+
+    test_interpret_ok!(
+        hoisted_mutual_recursion,
+        r#"
+        fun isEven(n) {
+          if (n == 0) return true;
+          return isOdd(n - 1);
+        }
+        fun isOdd(n) {
+          if (n == 0) return false;
+          return isEven(n - 1);
+        }
+        print isEven(10);
+        print isOdd(7);
+        "#,
+        "true\ntrue\n"
+    );
+
+    test_interpret_ok!(
+        local_function_is_not_hoisted,
+        r#"
+        fun f() { return "global"; }
+        {
+          fun g() { return f(); }
+          print g();
+          fun f() { return "local"; }
+          print g();
+          print f();
+        }
+        "#,
+        "\"global\"\n\"global\"\n\"local\"\n"
+    );
+
+    test_interpret_err!(
+        local_mutual_recursion_needs_declaration_order,
+        r#"
+        {
+          fun isEven(n) { if (n == 0) return true; return isOdd(n - 1); }
+          fun isOdd(n) { if (n == 0) return false; return isEven(n - 1); }
+        }
+        "#,
+        "Undefined variable 'isOdd'."
+    );
+
+    #[test]
+    fn hoisted_function_called_before_declaration() {
+        let (result, _) = test_interpret!("f(); fun f() {}", |interpreter| interpreter);
+        let err = result.expect_err("expected an error");
+        assert_eq!(err.line_report(), "[line 1] Error: Undefined variable 'f'.");
+    }
+
+    #[test]
+    fn undefined_variable_reports_position() {
+        let (result, _) =
+            test_interpret!("print 1;\n  g();\nfun g() {}", |interpreter| interpreter);
+        let err = result.expect_err("expected an error");
+        assert_eq!(err.to_string(), "Undefined variable 'g'.");
+        assert_eq!(err.span(), Some(Span::new(2, 3)));
+    }
+
+    test_interpret_ok!(
+        instance_equality_is_identity,
+        r#"
+        class A {}
+        var a = A();
+        var b = a;
+        print a == b;
+        print a == A();
+        "#,
+        "true\nfalse\n"
+    );
+
+    fn with_double(
+        mut interpreter: Interpreter<crate::test_utils::TestWriter>,
+    ) -> Interpreter<crate::test_utils::TestWriter> {
+        interpreter.register_native(
+            "double",
+            1,
+            Rc::new(|args| Ok(Object::from(args[0].try_into_i64()? * 2))),
+        );
+        interpreter
+    }
+
+    test_interpret_ok!(
+        register_native,
+        "print double(21); print double;",
+        "42\n<native fn double>\n",
+        with_double
+    );
+
+    test_interpret_err!(
+        register_native_error,
+        r#"double("x");"#,
+        "Expected integer but got string.",
+        with_double
+    );
+
+    test_interpret_err!(
+        register_native_arity,
+        "double(1, 2);",
+        "Expected 1 arguments but got 2.",
+        with_double
+    );
+
+    test_interpret_ok!(
+        read_defined_global,
+        r#"fun banner() { return "lox " + VERSION; } print banner(); VERSION = "2.0"; print VERSION;"#,
+        "\"lox 1.0\"\n\"2.0\"\n",
+        |mut interpreter| {
+            interpreter.define_global("VERSION", Object::string("1.0"));
+            interpreter
+        }
+    );
+
+    test_interpret_ok!(
+        native_returns_constructed_objects,
+        r#"print make(0); print make(1); print make(2); print make(3);"#,
+        "nil\n7\n1.5\n\"made\"\n",
+        |mut interpreter| {
+            interpreter.register_native(
+                "make",
+                1,
+                Rc::new(|args| {
+                    Ok(match args[0].try_into_i64()? {
+                        0 => Object::nil(),
+                        1 => Object::int(7),
+                        2 => Object::float(1.5),
+                        _ => Object::string("made"),
+                    })
+                }),
+            );
+            interpreter
+        }
+    );
+
+    #[test]
+    fn trace_statements_to_error_writer() {
+        let input = "var a = 1;\nprint a + 1;";
+        let tokens: std::result::Result<Vec<_>, _> =
+            crate::lexer::Lexer::new(input.chars()).unwrap().collect();
+        let mut stmts = crate::parser::Parser::new(tokens.unwrap().into_iter())
+            .program()
+            .unwrap();
+        let mut interpreter = Interpreter::new(Vec::new())
+            .with_error_writer(Vec::new())
+            .with_trace(true);
+        crate::Resolver::new()
+            .resolve(&mut stmts, &mut interpreter)
+            .and_then(|_| interpreter.run_many(&stmts))
+            .unwrap();
+        assert_eq!(String::from_utf8(interpreter.writer).unwrap(), "2\n");
+        assert_eq!(
+            String::from_utf8(interpreter.error_writer).unwrap(),
+            "[line 1] var\n[line 2] print\n"
+        );
+    }
+
+    #[test]
+    fn by_name_ignores_use_site() {
+        let input = "var a = 1; print a; a = a + 1; print a;";
+        let tokens: std::result::Result<Vec<_>, _> =
+            crate::lexer::Lexer::new(input.chars()).unwrap().collect();
+        let mut stmts = crate::parser::Parser::new(tokens.unwrap().into_iter())
+            .program()
+            .unwrap();
+        let mut interpreter = Interpreter::new(crate::test_utils::TestWriter::new());
+        crate::Resolver::new()
+            .resolve(&mut stmts, &mut interpreter)
+            .and_then(|_| interpreter.run_many(&stmts))
+            .unwrap();
+
+        let (first, second) = match (&stmts[1], &stmts[3]) {
+            (Stmt::Print(Expr::Ident(first)), Stmt::Print(Expr::Ident(second))) => (first, second),
+            _ => panic!("expected two prints of `a`"),
+        };
+        assert_ne!(first, second);
+        assert_ne!(first.rid, second.rid);
+        assert_eq!(interpreter.by_name("a"), Some(Object::Int(2)));
+        assert_eq!(interpreter.by_name("b"), None);
+        assert!(matches!(
+            interpreter.by_name("str"),
+            Some(Object::NativeFunction(_))
+        ));
+    }
+
+    test_interpret_ok!(
+        param_annotations_ignored_by_default,
+        r#"fun add(a: int, b: int) { return a + b; } print add("a", "b");"#,
+        "\"ab\"\n"
+    );
+
+    test_interpret_ok!(
+        param_annotations_checked,
+        r#"
+        fun add(a: int, b: number) { return a + b; }
+        fun show(s: string, x: any, f: function) { print s; }
+        print add(1, 2.5);
+        show("ok", nil, show);
+        "#,
+        "3.5\n\"ok\"\n",
+        |interpreter| interpreter.with_param_type_checks(true)
+    );
+
+    test_interpret_err!(
+        param_annotation_mismatch,
+        r#"fun add(a: int, b: int) { return a + b; } add(1, "2");"#,
+        "Expected int for parameter 'b' but got string.",
+        |interpreter| interpreter.with_param_type_checks(true)
+    );
+
+    test_interpret_ok!(
+        float_precision,
+        r#"print 1.0 / 3.0; print 2.5; print 7; print "1.5"; print str(1.0 / 3.0);"#,
+        "0.3333\n2.5000\n7\n\"1.5\"\n\"0.3333333333333333\"\n",
+        |interpreter| interpreter.with_float_precision(4)
+    );
+
+    /// Guards the size of a call's Rust frames, which bounds how deep ordinary
+    /// (non-tail) recursion gets before the stack overflows.
+    #[test]
+    fn non_tail_recursion_depth() {
+        let depth = if cfg!(debug_assertions) { 70 } else { 1300 };
+        let run = move || {
+            let input = format!(
+                "fun f(n) {{ if (n == 0) return 0; return 1 + f(n - 1); }} print f({});",
+                depth
+            );
+            let (result, output) = test_interpret!(input.as_str(), |interpreter| interpreter);
+            (result.map_err(|e| e.to_string()), output)
+        };
+        let (result, output) = std::thread::Builder::new()
+            .stack_size(8 << 20)
+            .spawn(run)
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(result, Ok(()));
+        assert_eq!(output, format!("{}\n", depth));
+    }
+
+    test_interpret_ok!(
+        tail_call_does_not_overflow,
+        r#"
+        fun count(n, acc) {
+            if (n == 0) return acc;
+            return count(n - 1, acc + 1);
+        }
+        print count(1000000, 0);
+        "#,
+        "1000000\n"
+    );
+
+    test_interpret_ok!(
+        tail_call_keeps_closures_per_iteration,
+        r#"
+        var first;
+        fun loop(n) {
+            fun show() { print n; }
+            if (n == 3) first = show;
+            if (n == 0) return show;
+            return loop(n - 1);
+        }
+        loop(3)();
+        first();
+        "#,
+        "0\n3\n"
+    );
+
+    test_interpret_ok!(
+        return_call_of_same_body_other_closure,
+        r#"
+        fun make(tag) {
+            fun f(n, other) {
+                if (n == 0) return tag;
+                return other(n - 1, nil);
+            }
+            return f;
+        }
+        print make("a")(1, make("b"));
+        "#,
+        "\"b\"\n"
+    );
+
+    test_interpret_ok!(
+        return_call_of_other_function,
+        r#"
+        fun double(x) { return x * 2; }
+        fun f(x) { return double(x); }
+        fun outer(n) {
+            {
+                var local = n;
+                if (n > 0) return outer(n - 1);
+            }
+            return f(n + 1);
+        }
+        print outer(3);
+        "#,
+        "2\n"
+    );
+
+    test_interpret_ok!(
+        params_bound_by_position,
+        r#"
+        fun fib(n) {
+            if (n < 2) return n;
+            return fib(n - 1) + fib(n - 2);
+        }
+        print fib(10);
+
+        fun counter(n) {
+            fun inc() {
+                n = n + 1;
+                return n;
+            }
+            return inc;
+        }
+        var a = counter(0);
+        var b = counter(10);
+        a();
+        print a();
+        print b();
+
+        fun shadow(x, y) {
+            {
+                var x = "inner";
+                print x + y;
+            }
+            return x;
+        }
+        print shadow(1, "!");
+        "#,
+        "55\n2\n11\n\"inner!\"\n1\n"
+    );
+
+    test_interpret_ok!(
+        for_each_over_iterator_protocol,
+        r#"
+        class CountdownIter {
+            init(n) { this.n = n; }
+            has_next() { return this.n > 0; }
+            next() {
+                var n = this.n;
+                this.n = this.n - 1;
+                return n;
+            }
+        }
+        class Countdown {
+            init(from) { this.from = from; }
+            iter() { return CountdownIter(this.from); }
+        }
+        var countdown = Countdown(3);
+        for (var i in countdown) print i;
+        for (var i in countdown) {
+            if (i == 1) break;
+            print i * 10;
+        }
+        "#,
+        "3\n2\n1\n30\n20\n"
+    );
+
+    test_interpret_err!(
+        for_each_requires_iter_method,
+        "class A {} for (var x in A()) print x;",
+        "Undefined property 'iter'."
+    );
+
+    test_interpret_ok!(
+        super_method_is_bound_to_this,
+        r#"
+        class A {
+            name() { return this.n; }
+        }
+        class B < A {
+            init() { this.n = "b"; }
+            getter() { return super.name; }
+        }
+        var name = B().getter();
+        print name();
+        "#,
+        "\"b\"\n"
+    );
+
+    test_interpret_err!(
+        super_does_not_see_fields,
+        r#"
+        class A {
+            init() { this.field = 1; }
+        }
+        class B < A {
+            read() { return super.field; }
+        }
+        print B().read();
+        "#,
+        "Undefined property 'field'."
+    );
+
+    test_interpret_ok!(
+        method_calls_later_method,
+        r#"
+        class A {
+            method0() { return this.method1() + 1; }
+            method1() { return 41; }
+        }
+        print A().method0();
+        "#,
+        "42\n"
+    );
+
+    #[test]
+    fn print_hook_sees_printed_values() {
+        let printed = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = Interpreter::new(Vec::new());
+        let sink = Rc::clone(&printed);
+        interpreter.set_print_hook(Box::new(move |o| sink.borrow_mut().push(o.clone())));
+        interpreter
+            .run_many(&[
+                Stmt::Print(Expr::Int(1)),
+                Stmt::Print(Expr::String("a".into())),
+            ])
+            .unwrap();
+        assert_eq!(
+            *printed.borrow(),
+            vec![Object::Int(1), Object::String("a".into())]
+        );
+        assert_eq!(String::from_utf8_lossy(&interpreter.writer), "1\n\"a\"\n");
+    }
 }
@@ -1,6 +1,11 @@
 use std::fs::read_to_string;
+use std::io::sink;
+use std::io::stdin;
 use std::io::stdout;
+use std::io::IsTerminal;
+use std::io::Read;
 use std::io::Write;
+use std::process::ExitCode;
 
 extern crate anyhow;
 use anyhow::anyhow;
@@ -20,20 +25,87 @@ use rustyline::Editor;
 extern crate thiserror;
 use thiserror::Error;
 
+#[cfg(test)]
+macro_rules! test_interpret {
+    ($input: expr, $configure: expr) => {{
+        let fake_stdout = $crate::test_utils::TestWriter::new();
+        let result = {
+            let input = $input;
+            let lexer = $crate::lexer::Lexer::new(input.chars()).unwrap();
+            let tokens: std::result::Result<Vec<$crate::lexer::Token>, _> =
+                lexer.into_iter().collect();
+            let tokens = tokens.expect("lexing error");
+            let mut stmts = $crate::parser::Parser::new(tokens.into_iter())
+                .program()
+                .expect("parsing error");
+
+            dbg!($input);
+            let configure: fn(
+                $crate::Interpreter<$crate::test_utils::TestWriter>,
+            ) -> $crate::Interpreter<$crate::test_utils::TestWriter> = $configure;
+            let mut interpreter = configure($crate::Interpreter::new(fake_stdout.clone()));
+            let mut resolver = $crate::Resolver::new();
+            resolver
+                .resolve(&mut stmts, &mut interpreter)
+                .and_then(|_| interpreter.run_many(&stmts))
+        };
+        (result, fake_stdout.into_string())
+    }};
+}
+
+#[cfg(test)]
+macro_rules! test_interpret_ok {
+    ($name: ident,$input: literal,$tt: expr) => {
+        test_interpret_ok!($name, $input, $tt, |interpreter| interpreter);
+    };
+    ($name: ident,$input: literal,$tt: expr,$configure: expr) => {
+        #[test]
+        fn $name() {
+            let (result, output) = test_interpret!($input, $configure);
+            result.expect("interpret error");
+            assert_eq!(&output, $tt);
+        }
+    };
+}
+
+#[cfg(test)]
+macro_rules! test_interpret_err {
+    ($name: ident,$input: literal,$err: expr) => {
+        test_interpret_err!($name, $input, $err, |interpreter| interpreter);
+    };
+    ($name: ident,$input: literal,$err: expr,$configure: expr) => {
+        #[test]
+        fn $name() {
+            let (result, _) = test_interpret!($input, $configure);
+            assert_eq!(result.expect_err("expected an error").to_string(), $err);
+        }
+    };
+}
+
 mod callable;
 
+mod diagnostics;
+pub use diagnostics::analyze;
+pub use diagnostics::Diagnostic;
+pub use diagnostics::Diagnostics;
+pub use diagnostics::Severity;
+
 mod evaluator;
 use evaluator::EvalResult;
 use evaluator::Evaluator;
 
 mod interpreter;
-use interpreter::Interpreter;
+pub use interpreter::Interpreter;
+pub use interpreter::Permissions;
+pub use interpreter::PreparedProgram;
+
+mod native;
 
 mod resolver;
-use resolver::Resolver;
+pub use resolver::Resolver;
 
 fn prompt() {
-    let mut interpreter = Interpreter::new(stdout());
+    let mut interpreter = Interpreter::new(stdout()).with_error_writer(stdout());
     let mut resolver = Resolver::new();
     let mut rl = Editor::<()>::new();
     if rl.load_history("history.txt").is_err() {
@@ -45,7 +117,7 @@ fn prompt() {
                 rl.add_history_entry(line.as_str());
                 match runline(line, &mut interpreter, &mut resolver) {
                     Err(e) => {
-                        println!("Error in repl: {}", e);
+                        let _ = writeln!(interpreter.error_writer, "Error in repl: {}", e);
                         continue;
                     }
                     _ => continue,
@@ -61,11 +133,14 @@ fn prompt() {
     rl.save_history("history.txt").unwrap();
 }
 
-fn runline<W: Write>(
+fn runline<W: Write, E: Write>(
     line: String,
-    interpreter: &mut Interpreter<W>,
+    interpreter: &mut Interpreter<W, E>,
     resolver: &mut Resolver,
 ) -> Result<()> {
+    if line.trim().is_empty() {
+        return Ok(());
+    }
     let lexer = lexer::Lexer::new(line.chars()).unwrap();
     let tokens: std::result::Result<Vec<lexer::Token>, _> = lexer.into_iter().collect();
     let tokens: Vec<lexer::Token> = tokens?;
@@ -79,41 +154,148 @@ fn runline<W: Write>(
     } else {
         stmts
     };
-    resolver.resolve(&mut stmts, interpreter)?;
-    interpreter.run_many(&stmts)?;
-    Ok(())
+    // A line that fails part way would otherwise leave the resolver inside its scopes or
+    // with its declarations marked, breaking every later line.
+    let snapshot = resolver.clone();
+    let res = resolver
+        .resolve(&mut stmts, interpreter)
+        .and_then(|_| interpreter.run_many(&stmts));
+    if res.is_err() {
+        *resolver = snapshot;
+    }
+    res
 }
 
-fn runfile_stdout(file: &str) {
-    let mut interpreter = Interpreter::new(stdout());
-    match runfile(file, &mut interpreter) {
-        Ok(()) => {}
+/// Exit status of a program that fails to compile, as in the reference implementation.
+const EXIT_COMPILE_ERROR: u8 = 65;
+
+/// Exit status of a program that fails while running.
+const EXIT_RUNTIME_ERROR: u8 = 70;
+
+/// Program output goes to stdout; errors and trace lines go to stderr.
+fn runfile_stdout(file: &str, step_limit: Option<usize>, trace: bool) -> ExitCode {
+    let mut interpreter = Interpreter::new(stdout()).with_trace(trace);
+    if file != "-" {
+        interpreter = interpreter.with_script_path(file);
+    }
+    if let Some(limit) = step_limit {
+        interpreter = interpreter.with_step_limit(limit);
+    }
+    let stmts = match read_file(file).and_then(|source| compile(&source, &mut interpreter)) {
+        Ok(stmts) => stmts,
         Err(e) => {
-            println!("{}", e);
+            interpreter.report_error(&e);
+            return ExitCode::from(EXIT_COMPILE_ERROR);
+        }
+    };
+    match interpreter.run_many(&stmts) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            interpreter.report_error(&e);
+            ExitCode::from(EXIT_RUNTIME_ERROR)
         }
     }
 }
 
-fn runfile<W: Write>(file: &str, interpreter: &mut Interpreter<W>) -> Result<()> {
-    let program = read_to_string(file).map_err(|e| {
-        ErrorOrCtxJmp::Error(anyhow!("unable to read file {} with error {}", file, e))
-    })?;
-    let lexer = Lexer::new(program.chars()).unwrap();
-    let tokens: std::result::Result<Vec<lexer::Token>, _> = lexer.into_iter().collect();
-    let tokens = tokens?;
-    let mut stmts = Parser::new(tokens.into_iter()).program()?;
-    let mut resolver = Resolver::new();
-    resolver.resolve(&mut stmts, interpreter)?;
-    interpreter.run_many(&stmts)
+/// Reads the program in `file`, or from stdin when `file` is `-`.
+fn read_file(file: &str) -> Result<String> {
+    if file == "-" {
+        let mut source = String::new();
+        stdin()
+            .read_to_string(&mut source)
+            .map_err(|e| ErrorOrCtxJmp::Error(anyhow!("unable to read stdin with error {}", e)))?;
+        return Ok(source);
+    }
+    read_to_string(file)
+        .map_err(|e| ErrorOrCtxJmp::Error(anyhow!("unable to read file {} with error {}", file, e)))
+}
+
+fn parse_source(source: &str) -> Result<Vec<ast::Stmt>> {
+    let mut lexer = Lexer::new(source.chars()).unwrap();
+    let tokens: std::result::Result<Vec<lexer::Token>, _> = lexer.by_ref().collect();
+    let tokens = tokens.map_err(|e| ErrorOrCtxJmp::from(e).at(lexer.span()))?;
+    Ok(Parser::new_with_source(tokens.into_iter(), source).program()?)
+}
+
+/// Parses and resolves `source` against `interpreter`, ready to run.
+fn compile<W: Write, E: Write>(
+    source: &str,
+    interpreter: &mut Interpreter<W, E>,
+) -> Result<Vec<ast::Stmt>> {
+    let mut stmts = parse_source(source)?;
+    if interpreter.fold_constants {
+        ast::fold_constants(&mut stmts);
+    }
+    Resolver::new().resolve(&mut stmts, interpreter)?;
+    Ok(stmts)
+}
+
+/// Lexes, parses and resolves `source` without executing it, reporting any static error.
+pub fn check_source(source: &str) -> Result<()> {
+    compile(source, &mut Interpreter::new(sink())).map(|_| ())
+}
+
+/// Runs `source` to completion and returns everything it printed.
+pub fn run_source(source: &str) -> Result<String> {
+    let mut interpreter = Interpreter::new(Vec::new());
+    let stmts = compile(source, &mut interpreter)?;
+    interpreter.run_many(&stmts)?;
+    Ok(String::from_utf8_lossy(&interpreter.writer).into_owned())
+}
+
+/// Parses `source` and returns its AST as JSON.
+pub fn ast_json(source: &str) -> Result<String> {
+    Ok(ast::to_json(&parse_source(source)?))
+}
+
+fn checkfile(file: &str) -> Result<()> {
+    let mut interpreter = Interpreter::new(sink()).with_script_path(file);
+    compile(&read_file(file)?, &mut interpreter).map(|_| ())
 }
 
 pub struct Runner {}
 
 impl Runner {
-    pub fn run(file: Option<&String>) {
+    pub fn run(file: Option<&String>) -> ExitCode {
         match file {
-            Some(s) => runfile_stdout(s as &str),
-            None => prompt(),
+            Some(s) => runfile_stdout(s as &str, None, false),
+            // A program piped in without a filename runs like `-`.
+            None if !stdin().is_terminal() => runfile_stdout("-", None, false),
+            None => {
+                prompt();
+                ExitCode::SUCCESS
+            }
+        }
+    }
+
+    pub fn ast_json(file: &str) -> ExitCode {
+        match read_file(file).and_then(|source| ast_json(&source)) {
+            Ok(json) => {
+                println!("{}", json);
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                ExitCode::from(EXIT_COMPILE_ERROR)
+            }
+        }
+    }
+
+    pub fn run_with_step_limit(file: &str, limit: usize) -> ExitCode {
+        runfile_stdout(file, Some(limit), false)
+    }
+
+    pub fn run_with_trace(file: &str) -> ExitCode {
+        runfile_stdout(file, None, true)
+    }
+
+    pub fn check(file: &str) -> ExitCode {
+        match checkfile(file) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("{}", e.line_report());
+                ExitCode::from(EXIT_COMPILE_ERROR)
+            }
         }
     }
 }
@@ -123,6 +305,13 @@ pub enum ErrorOrCtxJmp {
     #[error("{0}")]
     Error(#[from] anyhow::Error),
 
+    /// An error tied to a source location, raised at runtime or by the resolver.
+    #[error("{error}")]
+    Located {
+        error: anyhow::Error,
+        span: lexer::Span,
+    },
+
     #[error("{0}")]
     ParserError(#[from] parser::ParserErrorKind),
 
@@ -137,10 +326,427 @@ pub enum ErrorOrCtxJmp {
 
     #[error("Encountered a BrkJump, this is a BUG.")]
     BrkJump,
+
+    #[error("Encountered a ContJump, this is a BUG.")]
+    ContJump,
+
+    /// `return f(args);` inside `f` itself, see `FuncObject::call`.
+    #[error("Encountered a TailCall, this is a BUG.")]
+    TailCall { args: Vec<ast::Object> },
+}
+
+impl ErrorOrCtxJmp {
+    /// Attaches the location of the expression that raised a plain, lexer or environment
+    /// error. An undefined variable keeps the position of its name.
+    pub(crate) fn at(self, span: lexer::Span) -> Self {
+        match self {
+            ErrorOrCtxJmp::Error(error) => ErrorOrCtxJmp::Located { error, span },
+            ErrorOrCtxJmp::LexerError(e) => ErrorOrCtxJmp::Located {
+                error: e.into(),
+                span,
+            },
+            ErrorOrCtxJmp::EnvError(e) => ErrorOrCtxJmp::Located {
+                span: e.span().unwrap_or(span),
+                error: e.into(),
+            },
+            e => e,
+        }
+    }
+
+    /// Formats a static error the way the reference implementation does, as in
+    /// `[line 2] Error at 'b': Expect ';' after value.`.
+    pub fn line_report(&self) -> String {
+        let message = self.to_string();
+        let message = if message.starts_with("Error") {
+            message
+        } else {
+            format!("Error: {}", message)
+        };
+        match self.span() {
+            Some(span) => format!("[line {}] {}", span.line, message),
+            None => message,
+        }
+    }
+
+    /// The offending source line with a caret, for parser errors that kept one.
+    pub fn snippet(&self) -> Option<&str> {
+        match self {
+            ErrorOrCtxJmp::ParserError(e) => e.snippet(),
+            _ => None,
+        }
+    }
+
+    pub fn span(&self) -> Option<lexer::Span> {
+        match self {
+            ErrorOrCtxJmp::Located { span, .. } => Some(*span),
+            ErrorOrCtxJmp::ParserError(e) => e.span(),
+            ErrorOrCtxJmp::EnvError(e) => e.span(),
+            _ => None,
+        }
+    }
 }
 
 type Result<T> = std::result::Result<T, ErrorOrCtxJmp>;
 
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+
+    use super::*;
+
+    #[test]
+    fn check_rejects_this_outside_class() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("invalid.lox");
+        write(&file, "fun f() { print this; }").unwrap();
+        let e = checkfile(file.to_str().unwrap()).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Error at 'this': Can't use 'this' outside of a class."
+        );
+    }
+
+    #[test]
+    fn check_rejects_use_before_declaration() {
+        let e = check_source("{\n  print y;\n  var y;\n}").unwrap_err();
+        assert_eq!(e.to_string(), "Undefined variable 'y'.");
+        let span = e.span().expect("resolve error should carry a span");
+        assert_eq!((span.line, span.col), (2, 9));
+        assert!(check_source("print str(1);").is_ok());
+    }
+
+    #[test]
+    fn line_report_format() {
+        let e = check_source("var a = 1;\nprint a b;").unwrap_err();
+        assert_eq!(
+            e.line_report(),
+            "[line 2] Error at 'b': expected ; at the end of print statement"
+        );
+
+        let e = check_source("fun f() {\n  var a = 1;\n  var a = 2;\n}").unwrap_err();
+        assert_eq!(
+            e.line_report(),
+            "[line 3] Error at 'a': Already a variable with this name in this scope."
+        );
+
+        let e = check_source("print 1;\nprint \"open;").unwrap_err();
+        assert_eq!(e.line_report(), "[line 2] Error: Unterminated string.");
+
+        let e = check_source("{\n  print y;\n}").unwrap_err();
+        assert_eq!(e.line_report(), "[line 2] Error: Undefined variable 'y'.");
+
+        for (input, report) in [
+            (
+                "\nreturn 1;",
+                "[line 2] Error at 'return': Can't return from top-level code.",
+            ),
+            (
+                "\nbreak;",
+                "[line 2] Error at 'break': Can't break from top-level code.",
+            ),
+            (
+                "\ncontinue;",
+                "[line 2] Error at 'continue': Can't continue from top-level code.",
+            ),
+            (
+                "fun f(a,\n  a) {}",
+                "[line 2] Error at 'a': Already a variable with this name in this scope.",
+            ),
+            (
+                "1\n  = 2;",
+                "[line 2] Error at '=': Invalid assignment target.",
+            ),
+            (
+                "class A {\n  init() { return 1; }\n}",
+                "[line 2] Error at 'return': Can't return a value from an initializer.",
+            ),
+            (
+                "{\n  import \"x.lox\";\n}",
+                "[line 2] Error at 'import': Can only import at top level.",
+            ),
+        ] {
+            assert_eq!(check_source(input).unwrap_err().line_report(), report);
+        }
+    }
+
+    #[test]
+    fn constant_folding() {
+        let mut interpreter = Interpreter::new(Vec::new()).with_constant_folding(true);
+        let stmts = compile("print 1 + 2 * 3; print 1 / 0;", &mut interpreter).unwrap();
+        assert_eq!(stmts[0], ast::Stmt::Print(ast::Expr::Int(7)));
+        assert!(matches!(
+            stmts[1],
+            ast::Stmt::Print(ast::Expr::Binary(ast::BinaryOp::Div, _, _, _))
+        ));
+
+        let e = interpreter.run_many(&stmts).unwrap_err();
+        assert_eq!(e.to_string(), "Cannot divide by 0.");
+        assert_eq!(String::from_utf8_lossy(&interpreter.writer), "7\n");
+    }
+
+    #[test]
+    fn import_runs_file_relative_to_importer() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        write(
+            dir.join("lib/greet.lox"),
+            "import \"../main.lox\";\nfun greet(name) { return \"hi \" + name; }",
+        )
+        .unwrap();
+        let main = dir.join("main.lox");
+        write(
+            &main,
+            "import \"lib/greet.lox\";\nimport \"lib/greet.lox\";\nprint greet(\"lox\");",
+        )
+        .unwrap();
+
+        let mut interpreter = Interpreter::new(Vec::new()).with_script_path(&main);
+        let stmts = compile(
+            &read_file(main.to_str().unwrap()).unwrap(),
+            &mut interpreter,
+        )
+        .unwrap();
+        interpreter.run_many(&stmts).unwrap();
+        assert_eq!(String::from_utf8_lossy(&interpreter.writer), "\"hi lox\"\n");
+
+        let mut interpreter = Interpreter::sandboxed(Vec::new()).with_script_path(&main);
+        let e = compile(
+            &read_file(main.to_str().unwrap()).unwrap(),
+            &mut interpreter,
+        )
+        .unwrap_err();
+        assert_eq!(e.to_string(), "Permission denied: filesystem access.");
+
+        let e = check_source("{ import \"a.lox\"; }").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Error at 'import': Can only import at top level."
+        );
+    }
+
+    #[test]
+    fn import_runs_the_file_the_resolver_parsed() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let lib = dir.join("lib.lox");
+        write(&lib, "var version = 1;").unwrap();
+
+        let mut interpreter = Interpreter::new(Vec::new()).with_script_path(dir.join("main.lox"));
+        let stmts = compile("import \"lib.lox\";\nprint version;", &mut interpreter).unwrap();
+        write(&lib, "var version = 2;").unwrap();
+        interpreter.run_many(&stmts).unwrap();
+        assert_eq!(String::from_utf8_lossy(&interpreter.writer), "1\n");
+    }
+
+    #[test]
+    fn import_as_module_keeps_names_apart() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        write(
+            dir.join("a.lox"),
+            "var name = \"a\"; fun describe() { return str(name); }",
+        )
+        .unwrap();
+        write(
+            dir.join("b.lox"),
+            "var name = \"b\"; fun describe() { return name + name; }",
+        )
+        .unwrap();
+        write(dir.join("self.lox"), "import \"self.lox\" as me;").unwrap();
+        let main = dir.join("main.lox");
+        write(
+            &main,
+            "import \"a.lox\" as a;\nimport \"b.lox\" as b;\nprint a.describe();\nprint b.describe();\nprint a;\nprint a.missing;",
+        )
+        .unwrap();
+
+        let mut interpreter = Interpreter::new(Vec::new()).with_script_path(&main);
+        let stmts = compile(
+            &read_file(main.to_str().unwrap()).unwrap(),
+            &mut interpreter,
+        )
+        .unwrap();
+        let e = interpreter.run_many(&stmts).unwrap_err();
+        assert_eq!(e.to_string(), "Undefined property 'missing'.");
+        assert_eq!(
+            String::from_utf8_lossy(&interpreter.writer),
+            "\"a\"\n\"bb\"\n<module a>\n"
+        );
+        assert!(interpreter.by_name("describe").is_none());
+
+        let e = check_source("var a; import \"a.lox\" as a;").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Error at 'a': Already a variable with this name in this scope."
+        );
+
+        let mut interpreter = Interpreter::new(Vec::new()).with_script_path(dir.join("main.lox"));
+        let stmts = compile("import \"self.lox\" as s;", &mut interpreter).unwrap();
+        let e = interpreter.run_many(&stmts).unwrap_err();
+        assert!(e.to_string().starts_with("Circular import of"), "{}", e);
+    }
+
+    #[test]
+    fn import_as_module_sees_builtins() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        write(
+            dir.join("circle.lox"),
+            "fun area(r) { return PI * r * r; } var twice = double(2);",
+        )
+        .unwrap();
+
+        let mut interpreter = Interpreter::new(Vec::new()).with_script_path(dir.join("main.lox"));
+        interpreter.register_native(
+            "double",
+            1,
+            std::rc::Rc::new(|args| Ok(ast::Object::from(args[0].try_into_i64()? * 2))),
+        );
+        let stmts = compile(
+            "import \"circle.lox\" as c;\nprint c.area(1) == PI;\nprint c.twice;",
+            &mut interpreter,
+        )
+        .unwrap();
+        interpreter.run_many(&stmts).unwrap();
+        assert_eq!(String::from_utf8_lossy(&interpreter.writer), "true\n4\n");
+    }
+
+    #[test]
+    fn check_accepts_valid_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("valid.lox");
+        write(&file, "class A { f() { print this; } } A().f();").unwrap();
+        assert!(checkfile(file.to_str().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn run_empty_source() {
+        assert_eq!(run_source("").unwrap(), "");
+        assert_eq!(run_source(" \n\t\n").unwrap(), "");
+        assert_eq!(run_source("// nothing to see here").unwrap(), "");
+        assert_eq!(run_source("print 1;").unwrap(), "1\n");
+    }
+
+    #[test]
+    fn repl_env_survives_error_in_function() {
+        let mut interpreter = Interpreter::new(Vec::new());
+        let mut resolver = Resolver::new();
+        for line in [
+            "var a = 1;",
+            "fun f() { var a = 2; { var b = 3; return 1 / 0; } }",
+        ] {
+            runline(line.into(), &mut interpreter, &mut resolver).unwrap();
+        }
+        let e = runline("f();".into(), &mut interpreter, &mut resolver).unwrap_err();
+        assert_eq!(e.to_string(), "Cannot divide by 0.");
+        runline("print a;".into(), &mut interpreter, &mut resolver).unwrap();
+        runline(
+            "{ var c = a + 1; print c; }".into(),
+            &mut interpreter,
+            &mut resolver,
+        )
+        .unwrap();
+        assert_eq!(String::from_utf8_lossy(&interpreter.writer), "1\n2\n");
+    }
+
+    #[test]
+    fn repl_resolver_recovers_from_failed_lines() {
+        let mut interpreter = Interpreter::new(Vec::new());
+        let mut resolver = Resolver::new();
+        let mut run = |line: &str| runline(line.into(), &mut interpreter, &mut resolver);
+        run("var a = 1;").unwrap();
+        run("{ var a = 2; print a; }").unwrap();
+        run("print a;").unwrap();
+        run("{ var b = nope; }").unwrap_err();
+        run("var b = 3; print b;").unwrap();
+        run("var c = 1 / 0;").unwrap_err();
+        run("var c = 4; print a + c;").unwrap();
+        assert_eq!(String::from_utf8_lossy(&interpreter.writer), "2\n1\n3\n5\n");
+    }
+
+    #[test]
+    fn run_prepared_program_repeatedly() {
+        let source = "var n = 0; fun inc() { n = n + 1; return n; } inc(); print inc();";
+        let mut interpreter = Interpreter::new(Vec::new());
+        let program = interpreter.prepare(parse_source(source).unwrap()).unwrap();
+        interpreter.run_prepared(&program).unwrap();
+        interpreter.run_prepared(&program).unwrap();
+        assert_eq!(String::from_utf8_lossy(&interpreter.writer), "2\n2\n");
+
+        let mut fresh = Interpreter::new(Vec::new());
+        fresh.run_prepared(&program).unwrap();
+        assert_eq!(String::from_utf8_lossy(&fresh.writer), "2\n");
+    }
+
+    #[test]
+    fn separate_error_writer() {
+        let mut interpreter = Interpreter::new(Vec::new()).with_error_writer(Vec::new());
+        let mut resolver = Resolver::new();
+        runline("print 1;".into(), &mut interpreter, &mut resolver).unwrap();
+        let e = runline("print 2; nil();".into(), &mut interpreter, &mut resolver).unwrap_err();
+        interpreter.report_error(&e);
+        assert_eq!(String::from_utf8_lossy(&interpreter.writer), "1\n2\n");
+        assert_eq!(
+            String::from_utf8_lossy(&interpreter.error_writer),
+            "[line 1] Error: Can only call functions and classes.\n"
+        );
+    }
+
+    #[test]
+    fn report_parse_error_once_with_snippet() {
+        let mut interpreter = Interpreter::new(Vec::new()).with_error_writer(Vec::new());
+        let e = compile("var a = 1;\nprint a b;", &mut interpreter).unwrap_err();
+        interpreter.report_error(&e);
+        assert!(interpreter.writer.is_empty());
+        assert_eq!(
+            String::from_utf8_lossy(&interpreter.error_writer),
+            "[line 2] Error at 'b': expected ; at the end of print statement\nprint a b;\n        ^\n"
+        );
+    }
+
+    #[test]
+    fn blank_repl_line_is_a_no_op() {
+        let mut interpreter = Interpreter::new(Vec::new());
+        let mut resolver = Resolver::new();
+        assert!(runline("   ".into(), &mut interpreter, &mut resolver).is_ok());
+        assert!(interpreter.writer.is_empty());
+    }
+
+    #[test]
+    fn ast_json_tags_nodes() {
+        let json = ast_json("var a = 1; if (a > 0) print a + 2.5;").unwrap();
+        for tag in [
+            "\"VariableDecl\"",
+            "\"Int\":1",
+            "\"Conditional\"",
+            "\"Binary\":[\"Gt\"",
+            "\"Print\"",
+            "\"Float\":2.5",
+            "\"lexeme\":\"a\"",
+        ] {
+            assert!(json.contains(tag), "{} not in {}", tag, json);
+        }
+        assert!(ast_json("print ;").is_err());
+    }
+
+    #[test]
+    fn check_rejects_duplicate_method() {
+        let e = check_source("class A { f() {} g() {} f() {} }").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Error at 'f': Duplicate method name in class."
+        );
+        assert!(check_source("class A { f() {} } class B < A { f() {} }").is_ok());
+    }
+
+    #[test]
+    fn check_does_not_execute() {
+        assert!(check_source("var a = 1 / 0; print a;").is_ok());
+        assert!(check_source("print b;").is_err());
+    }
+}
+
 #[cfg(test)]
 mod test_utils {
     use std::cell::RefCell;
@@ -1,7 +1,39 @@
 use std::env::args;
+use std::process::ExitCode;
 
 use lox_interpreter::Runner;
 
-fn main() {
-    Runner::run(args().nth(1).as_ref());
+/// Exit status for a command line the interpreter does not understand.
+const EXIT_USAGE: u8 = 64;
+
+fn usage(message: &str) -> ExitCode {
+    eprintln!("{}", message);
+    ExitCode::from(EXIT_USAGE)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("--check") => match args.get(1) {
+            Some(file) => Runner::check(file),
+            None => usage("Usage: interpreter_main --check <file>"),
+        },
+        Some("--ast-json") => match args.get(1) {
+            Some(file) => Runner::ast_json(file),
+            None => usage("Usage: interpreter_main --ast-json <file>"),
+        },
+        Some("--max-steps") => match (args.get(1).map(|n| n.parse()), args.get(2)) {
+            (Some(Ok(limit)), Some(file)) => Runner::run_with_step_limit(file, limit),
+            _ => usage("Usage: interpreter_main --max-steps <n> <file>"),
+        },
+        Some("--version") => {
+            println!("lox-rs {}", env!("CARGO_PKG_VERSION"));
+            ExitCode::SUCCESS
+        }
+        Some("--trace") => match args.get(1) {
+            Some(file) => Runner::run_with_trace(file),
+            None => usage("Usage: interpreter_main --trace <file>"),
+        },
+        _ => Runner::run(args.first()),
+    }
 }
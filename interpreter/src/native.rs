@@ -0,0 +1,765 @@
+use std::fs;
+use std::io::Write;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use crate::anyhow;
+use crate::ast::*;
+use crate::ErrorOrCtxJmp;
+use crate::EvalResult;
+use crate::Interpreter;
+use crate::Result;
+
+pub(crate) const NATIVES: &[NativeFunction] = &[
+    NativeFunction {
+        name: "repeat",
+        arity: 2,
+    },
+    NativeFunction {
+        name: "trim",
+        arity: 1,
+    },
+    NativeFunction {
+        name: "starts_with",
+        arity: 2,
+    },
+    NativeFunction {
+        name: "ends_with",
+        arity: 2,
+    },
+    NativeFunction {
+        name: "contains",
+        arity: 2,
+    },
+    NativeFunction {
+        name: "index_of",
+        arity: 2,
+    },
+    NativeFunction {
+        name: "is_callable",
+        arity: 1,
+    },
+    NativeFunction {
+        name: "str",
+        arity: 1,
+    },
+    NativeFunction {
+        name: "range",
+        arity: 2,
+    },
+    NativeFunction {
+        name: "assert_eq",
+        arity: 2,
+    },
+    NativeFunction {
+        name: "write",
+        arity: 2,
+    },
+    NativeFunction {
+        name: "read",
+        arity: 1,
+    },
+    NativeFunction {
+        name: "dbg",
+        arity: 1,
+    },
+    NativeFunction {
+        name: "lox_version",
+        arity: 0,
+    },
+    NativeFunction {
+        name: "clamp",
+        arity: 3,
+    },
+    NativeFunction {
+        name: "sin",
+        arity: 1,
+    },
+    NativeFunction {
+        name: "cos",
+        arity: 1,
+    },
+    NativeFunction {
+        name: "tan",
+        arity: 1,
+    },
+    NativeFunction {
+        name: "random",
+        arity: 0,
+    },
+    NativeFunction {
+        name: "random_int",
+        arity: 2,
+    },
+    NativeFunction {
+        name: "fields",
+        arity: 1,
+    },
+];
+
+/// The xorshift64 generator behind `random` and `random_int`.
+#[derive(Debug, Clone)]
+pub(crate) struct XorShift(u64);
+
+impl XorShift {
+    pub(crate) fn new(seed: u64) -> Self {
+        // An all-zero state would only ever produce zeros.
+        Self(if seed == 0 {
+            0x9e37_79b9_7f4a_7c15
+        } else {
+            seed
+        })
+    }
+
+    pub(crate) fn from_time() -> Self {
+        Self::new(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos() as u64),
+        )
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+
+    /// Uniform in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// Kept out of line: inlined, the locals of every native end up in the stack frame of
+// each call to a script function, which limits how deep scripts can recurse.
+#[inline(never)]
+pub(crate) fn call<W: Write, E: Write>(
+    name: &str,
+    args: Vec<Object>,
+    ctx: &mut Interpreter<W, E>,
+) -> EvalResult {
+    match name {
+        "repeat" => repeat(args),
+        "trim" => trim(args),
+        "starts_with" => starts_with(args),
+        "ends_with" => ends_with(args),
+        "contains" => contains(args),
+        "index_of" => index_of(args),
+        "is_callable" => Ok(Object::Boolean(args[0].is_callable())),
+        "range" => match (&args[0], &args[1]) {
+            (Object::Int(start), Object::Int(end)) => Ok(Object::Range {
+                start: *start,
+                end: *end,
+            }),
+            _ => Err(ErrorOrCtxJmp::Error(anyhow!(
+                "Arguments to 'range' must be integers."
+            ))),
+        },
+        "assert_eq" => match args[0].equals(&args[1]) {
+            true => Ok(Object::Nil),
+            false => Err(ErrorOrCtxJmp::Error(anyhow!(
+                "Assertion failed: {} != {}",
+                args[0],
+                args[1]
+            ))),
+        },
+        "write" => write(args, ctx),
+        "read" => read(args, ctx),
+        "dbg" => dbg(args, ctx),
+        "lox_version" => Ok(Object::String(env!("CARGO_PKG_VERSION").into())),
+        "clamp" => clamp(args),
+        "sin" => Ok(Object::Float(expect_number("sin", &args[0])?.sin())),
+        "cos" => Ok(Object::Float(expect_number("cos", &args[0])?.cos())),
+        "tan" => Ok(Object::Float(expect_number("tan", &args[0])?.tan())),
+        "random" => Ok(Object::Float(ctx.rng.next_f64())),
+        "random_int" => random_int(args, ctx),
+        "fields" => fields(args),
+        "str" => Ok(match &args[0] {
+            Object::String(s) => Object::String(s.clone()),
+            o => Object::String(o.to_string()),
+        }),
+        _ => unreachable!("unknown native function {}", name),
+    }
+}
+
+fn repeat(args: Vec<Object>) -> EvalResult {
+    match (&args[0], &args[1]) {
+        (Object::String(s), Object::Int(n)) if *n >= 0 => {
+            // A string can hold at most `isize::MAX` bytes; past that `str::repeat` panics.
+            match usize::try_from(*n)
+                .ok()
+                .and_then(|n| s.len().checked_mul(n))
+                .filter(|&len| len <= isize::MAX as usize)
+            {
+                Some(_) => Ok(Object::String(s.repeat(*n as usize))),
+                None => Err(ErrorOrCtxJmp::Error(anyhow!(
+                    "Repeated string would be too long."
+                ))),
+            }
+        }
+        (Object::String(_), Object::Int(_)) => Err(ErrorOrCtxJmp::Error(anyhow!(
+            "Repeat count must be a non-negative integer."
+        ))),
+        _ => Err(ErrorOrCtxJmp::Error(anyhow!(
+            "Arguments to 'repeat' must be a string and an integer."
+        ))),
+    }
+}
+
+#[inline(always)]
+pub(crate) fn expect_filesystem_access<W, E>(ctx: &Interpreter<W, E>) -> Result<()> {
+    match ctx.permissions.filesystem {
+        true => Ok(()),
+        false => Err(ErrorOrCtxJmp::Error(anyhow!(
+            "Permission denied: filesystem access."
+        ))),
+    }
+}
+
+#[inline(always)]
+fn expect_string<'a>(native: &str, o: &'a Object) -> Result<&'a str> {
+    match o {
+        Object::String(s) => Ok(s),
+        _ => Err(ErrorOrCtxJmp::Error(anyhow!(
+            "Arguments to '{}' must be strings.",
+            native
+        ))),
+    }
+}
+
+fn expect_number(native: &str, o: &Object) -> Result<f64> {
+    match o {
+        Object::Int(i) => Ok(*i as f64),
+        Object::Float(f) => Ok(*f),
+        _ => Err(ErrorOrCtxJmp::Error(anyhow!(
+            "Arguments to '{}' must be numbers.",
+            native
+        ))),
+    }
+}
+
+/// Stays an integer when all three arguments are; otherwise works on floats.
+fn clamp(args: Vec<Object>) -> EvalResult {
+    let inverted = || {
+        ErrorOrCtxJmp::Error(anyhow!(
+            "Lower bound of 'clamp' must not be greater than upper bound."
+        ))
+    };
+    if let [Object::Int(x), Object::Int(lo), Object::Int(hi)] = args[..] {
+        return match lo <= hi {
+            true => Ok(Object::Int(x.clamp(lo, hi))),
+            false => Err(inverted()),
+        };
+    }
+    let x = expect_number("clamp", &args[0])?;
+    let lo = expect_number("clamp", &args[1])?;
+    let hi = expect_number("clamp", &args[2])?;
+    match lo <= hi {
+        true => Ok(Object::Float(x.clamp(lo, hi))),
+        false => Err(inverted()),
+    }
+}
+
+/// Inclusive of both bounds.
+fn random_int<W, E>(args: Vec<Object>, ctx: &mut Interpreter<W, E>) -> EvalResult {
+    match (&args[0], &args[1]) {
+        (Object::Int(lo), Object::Int(hi)) if lo <= hi => {
+            let span = (*hi as i128 - *lo as i128 + 1) as u128;
+            let offset = ctx.rng.next_u64() as u128 % span;
+            Ok(Object::Int((*lo as i128 + offset as i128) as i64))
+        }
+        (Object::Int(_), Object::Int(_)) => Err(ErrorOrCtxJmp::Error(anyhow!(
+            "Lower bound of 'random_int' must not be greater than upper bound."
+        ))),
+        _ => Err(ErrorOrCtxJmp::Error(anyhow!(
+            "Arguments to 'random_int' must be integers."
+        ))),
+    }
+}
+
+/// There is no list type, so the names come back sorted in one string: `"x, y"`.
+fn fields(args: Vec<Object>) -> EvalResult {
+    match &args[0] {
+        Object::Instance(instance) => {
+            let instance = instance.borrow();
+            let mut names: Vec<&str> = instance.field_names().map(String::as_str).collect();
+            names.sort_unstable();
+            Ok(Object::String(names.join(", ")))
+        }
+        _ => Err(ErrorOrCtxJmp::Error(anyhow!(
+            "Arguments to 'fields' must be instances."
+        ))),
+    }
+}
+
+fn write<W, E>(args: Vec<Object>, ctx: &Interpreter<W, E>) -> EvalResult {
+    expect_filesystem_access(ctx)?;
+    let path = expect_string("write", &args[0])?;
+    let content = expect_string("write", &args[1])?;
+    Ok(Object::Boolean(fs::write(path, content).is_ok()))
+}
+
+fn read<W, E>(args: Vec<Object>, ctx: &Interpreter<W, E>) -> EvalResult {
+    expect_filesystem_access(ctx)?;
+    let path = expect_string("read", &args[0])?;
+    Ok(fs::read_to_string(path).map_or(Object::Nil, Object::String))
+}
+
+/// Writes `<type>: <value>` to the error writer and returns the value, so it can wrap
+/// any expression.
+fn dbg<W, E: Write>(mut args: Vec<Object>, ctx: &mut Interpreter<W, E>) -> EvalResult {
+    let o = args.remove(0);
+    if writeln!(ctx.error_writer, "{}: {}", o.type_name(), o.render(true)).is_err() {
+        return Err(ErrorOrCtxJmp::Error(anyhow!("unable to write")));
+    }
+    Ok(o)
+}
+
+fn trim(args: Vec<Object>) -> EvalResult {
+    let s = expect_string("trim", &args[0])?;
+    Ok(Object::String(s.trim().into()))
+}
+
+fn starts_with(args: Vec<Object>) -> EvalResult {
+    let s = expect_string("starts_with", &args[0])?;
+    let prefix = expect_string("starts_with", &args[1])?;
+    Ok(Object::Boolean(s.starts_with(prefix)))
+}
+
+fn ends_with(args: Vec<Object>) -> EvalResult {
+    let s = expect_string("ends_with", &args[0])?;
+    let suffix = expect_string("ends_with", &args[1])?;
+    Ok(Object::Boolean(s.ends_with(suffix)))
+}
+
+fn contains(args: Vec<Object>) -> EvalResult {
+    let s = expect_string("contains", &args[0])?;
+    let sub = expect_string("contains", &args[1])?;
+    Ok(Object::Boolean(s.contains(sub)))
+}
+
+fn index_of(args: Vec<Object>) -> EvalResult {
+    let s = expect_string("index_of", &args[0])?;
+    let sub = expect_string("index_of", &args[1])?;
+    // Index in chars rather than bytes so multibyte strings index as expected.
+    Ok(Object::Int(match s.find(sub) {
+        Some(byte_idx) => s[..byte_idx].chars().count() as i64,
+        None => -1,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    test_interpret_ok!(
+        repeat_string,
+        r#"print repeat("ab", 3); print repeat("x", 0);"#,
+        "\"ababab\"\n\"\"\n"
+    );
+
+    test_interpret_ok!(print_native, "print repeat;", "<native fn repeat>\n");
+
+    test_interpret_ok!(
+        shadow_native,
+        r#"var repeat = "shadowed"; print repeat;"#,
+        "\"shadowed\"\n"
+    );
+
+    test_interpret_err!(
+        repeat_negative_count,
+        r#"repeat("x", -1);"#,
+        "Repeat count must be a non-negative integer."
+    );
+
+    test_interpret_err!(
+        repeat_too_long,
+        r#"print repeat("ab", 9223372036854775807);"#,
+        "Repeated string would be too long."
+    );
+
+    test_interpret_err!(
+        repeat_non_string,
+        "repeat(1, 2);",
+        "Arguments to 'repeat' must be a string and an integer."
+    );
+
+    test_interpret_err!(
+        repeat_arity,
+        r#"repeat("x");"#,
+        "Expected 2 arguments but got 1."
+    );
+
+    test_interpret_ok!(
+        trim_string,
+        r#"print trim("  lox  "); print trim(""); print trim(" 	");"#,
+        "\"lox\"\n\"\"\n\"\"\n"
+    );
+
+    test_interpret_ok!(
+        string_prefix_suffix,
+        r#"
+        print starts_with("lox-rs", "lox");
+        print starts_with("lox-rs", "rs");
+        print starts_with("lox-rs", "");
+        print ends_with("lox-rs", "rs");
+        print ends_with("lox-rs", "lox");
+        print ends_with("", "");
+        "#,
+        "true\nfalse\ntrue\ntrue\nfalse\ntrue\n"
+    );
+
+    test_interpret_err!(
+        trim_non_string,
+        "trim(nil);",
+        "Arguments to 'trim' must be strings."
+    );
+
+    test_interpret_err!(
+        starts_with_non_string,
+        r#"starts_with("lox", 1);"#,
+        "Arguments to 'starts_with' must be strings."
+    );
+
+    test_interpret_err!(
+        ends_with_arity,
+        r#"ends_with("lox");"#,
+        "Expected 2 arguments but got 1."
+    );
+
+    test_interpret_ok!(
+        string_contains,
+        r#"print contains("lox-rs", "x-r"); print contains("lox-rs", "clox");"#,
+        "true\nfalse\n"
+    );
+
+    test_interpret_ok!(
+        string_index_of,
+        r#"
+        print index_of("lox-rs", "rs");
+        print index_of("lox-rs", "clox");
+        print index_of("lox", "");
+        print index_of("A~¶Þॐஃ", "ॐ");
+        "#,
+        "4\n-1\n0\n4\n"
+    );
+
+    test_interpret_err!(
+        index_of_non_string,
+        r#"index_of("lox", nil);"#,
+        "Arguments to 'index_of' must be strings."
+    );
+
+    test_interpret_ok!(
+        str_native,
+        r#"print str(1.5); print str("s"); print str(nil) + str(true);"#,
+        "\"1.5\"\n\"s\"\n\"niltrue\"\n"
+    );
+
+    test_interpret_ok!(
+        string_interpolation,
+        r#"
+        var name = "lox";
+        print "sum=${1+2}";
+        print "${name}-rs";
+        print "a${"b${1}c"}d";
+        print "$5 ${nil}";
+        "#,
+        "\"sum=3\"\n\"lox-rs\"\n\"ab1cd\"\n\"$5 nil\"\n"
+    );
+
+    test_interpret_ok!(
+        string_interpolation_ignores_shadowed_str,
+        r#"
+        var str = "global";
+        print "${1}-${str}";
+        fun f() {
+          fun str(a) { return "hijacked"; }
+          return "${2}";
+        }
+        print f();
+        "#,
+        "\"1-global\"\n\"2\"\n"
+    );
+
+    test_interpret_ok!(
+        escaped_interpolation,
+        r#"
+        var x = 1;
+        print "\${x} is ${x}";
+        print "\$x \\${x}";
+        "#,
+        "\"${x} is 1\"\n\"\\$x \\${x}\"\n"
+    );
+
+    test_interpret_ok!(
+        range_for_each,
+        r#"
+        var sum = 0;
+        for (var i in range(1, 5)) sum = sum + i;
+        print sum;
+        print range(0, 10);
+        for (var i in range(3, 3)) print i;
+        for (var i in range(0, 10)) {
+          if (i == 2) break;
+          print i;
+        }
+        "#,
+        "10\n0..10\n0\n1\n"
+    );
+
+    test_interpret_ok!(
+        for_each_closures_capture_each_value,
+        r#"
+        var fs = nil;
+        var gs = nil;
+        for (var i in range(0, 2)) {
+          fun f() { print i; }
+          if (i == 0) fs = f; else gs = f;
+        }
+        fs();
+        gs();
+        "#,
+        "0\n1\n"
+    );
+
+    test_interpret_ok!(
+        clamp_to_range,
+        r#"
+        print clamp(-5, 0, 10);
+        print clamp(5, 0, 10);
+        print clamp(15, 0, 10);
+        print clamp(1.5, 0, 1);
+        print clamp(-1, 0.5, 2);
+        print clamp(0.25, 0, 1);
+        "#,
+        "0\n5\n10\n1\n0.5\n0.25\n"
+    );
+
+    test_interpret_err!(
+        clamp_inverted_bounds,
+        "clamp(1, 10, 0);",
+        "Lower bound of 'clamp' must not be greater than upper bound."
+    );
+
+    test_interpret_err!(
+        clamp_non_number,
+        r#"clamp("1", 0, 10);"#,
+        "Arguments to 'clamp' must be numbers."
+    );
+
+    test_interpret_ok!(
+        trigonometry,
+        r#"
+        fun close(a, b) { return a - b < 0.000001 and b - a < 0.000001; }
+        print close(sin(PI / 2), 1);
+        print close(cos(PI), -1);
+        print close(tan(PI / 4), 1);
+        print sin(0);
+        "#,
+        "true\ntrue\ntrue\n0\n"
+    );
+
+    test_interpret_err!(
+        trigonometry_non_number,
+        "sin(nil);",
+        "Arguments to 'sin' must be numbers."
+    );
+
+    #[test]
+    fn seeded_random_is_deterministic() {
+        let input = r#"
+        for (var i in range(0, 3)) print random();
+        for (var i in range(0, 20)) {
+          var n = random_int(-2, 2);
+          if (n < -2 or n > 2) print "out of range";
+          print n;
+        }
+        print random_int(7, 7);
+        "#;
+        let (result, output) = test_interpret!(input, |interpreter| interpreter.with_seed(42));
+        result.expect("interpret error");
+        let (_, again) = test_interpret!(input, |interpreter| interpreter.with_seed(42));
+        let (_, other) = test_interpret!(input, |interpreter| interpreter.with_seed(43));
+        assert_eq!(output, again);
+        assert_ne!(output, other);
+        assert!(!output.contains("out of range"));
+        assert!(output.ends_with("\n7\n"));
+        for line in output.lines().take(3) {
+            let f: f64 = line.parse().unwrap();
+            assert!((0.0..1.0).contains(&f), "{}", f);
+        }
+    }
+
+    test_interpret_err!(
+        random_int_inverted_bounds,
+        "random_int(2, 1);",
+        "Lower bound of 'random_int' must not be greater than upper bound."
+    );
+
+    test_interpret_ok!(
+        instance_fields,
+        r#"
+        class Point {
+          init(x) { this.x = x; }
+          norm() { return this.x; }
+        }
+        var p = Point(1);
+        p.y = 2;
+        print fields(p);
+        print contains(fields(p), "x") and contains(fields(p), "y");
+        class Empty {}
+        print fields(Empty());
+        "#,
+        "\"x, y\"\ntrue\n\"\"\n"
+    );
+
+    test_interpret_err!(
+        fields_non_instance,
+        "fields(1);",
+        "Arguments to 'fields' must be instances."
+    );
+
+    test_interpret_err!(
+        range_non_integer,
+        "range(1, 2.5);",
+        "Arguments to 'range' must be integers."
+    );
+
+    test_interpret_err!(
+        for_each_non_iterable,
+        "for (var x in 1) print x;",
+        "Can only iterate over ranges and instances, not number."
+    );
+
+    test_interpret_ok!(
+        is_callable,
+        r#"
+        fun f() {}
+        class A {}
+        print is_callable(f);
+        print is_callable(A);
+        print is_callable(is_callable);
+        print is_callable(A());
+        print is_callable("f");
+        "#,
+        "true\ntrue\ntrue\nfalse\nfalse\n"
+    );
+
+    test_interpret_ok!(
+        assert_eq_passes,
+        r#"
+        print assert_eq(1, 1.0);
+        assert_eq("a" + "b", "ab");
+        assert_eq(nil, nil);
+        class A {}
+        var a = A();
+        assert_eq(a, a);
+        "#,
+        "nil\n"
+    );
+
+    test_interpret_err!(
+        assert_eq_fails,
+        r#"assert_eq(1 + 1, "2");"#,
+        "Assertion failed: 2 != \"2\""
+    );
+
+    #[test]
+    fn write_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("write.txt");
+        let input = format!(r#"print write("{}", "written by lox");"#, path.display());
+        let (result, output) = test_interpret!(input.as_str(), |interpreter| interpreter);
+        result.expect("interpret error");
+        assert_eq!(output, "true\n");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "written by lox");
+
+        let (result, _) = test_interpret!(input.as_str(), |interpreter| interpreter
+            .with_permissions(crate::Permissions::none()));
+        assert_eq!(
+            result.expect_err("expected an error").to_string(),
+            "Permission denied: filesystem access."
+        );
+    }
+
+    #[test]
+    fn read_back_written_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("read.txt");
+        let input = format!(
+            r#"
+            var path = "{}";
+            write(path, "round trip");
+            print read(path);
+            print read(path + ".missing");
+            "#,
+            path.display()
+        );
+        let (result, output) = test_interpret!(input.as_str(), |interpreter| interpreter);
+        result.expect("interpret error");
+        assert_eq!(output, "\"round trip\"\nnil\n");
+    }
+
+    test_interpret_err!(
+        read_without_filesystem_access,
+        r#"read("x");"#,
+        "Permission denied: filesystem access.",
+        |interpreter| interpreter.with_permissions(crate::Permissions {
+            filesystem: false,
+            ..crate::Permissions::all()
+        })
+    );
+
+    #[test]
+    fn sandboxed_rejects_read() {
+        let tokens: std::result::Result<Vec<_>, _> =
+            crate::lexer::Lexer::new(r#"read("x");"#.chars())
+                .unwrap()
+                .collect();
+        let mut stmts = crate::parser::Parser::new(tokens.unwrap().into_iter())
+            .program()
+            .unwrap();
+        let mut interpreter = crate::Interpreter::sandboxed(Vec::new());
+        let e = crate::Resolver::new()
+            .resolve(&mut stmts, &mut interpreter)
+            .and_then(|_| interpreter.run_many(&stmts))
+            .unwrap_err();
+        assert_eq!(e.to_string(), "Permission denied: filesystem access.");
+    }
+
+    test_interpret_ok!(
+        lox_version_is_crate_version,
+        r#"var v = lox_version(); print v != "" and v == str(v);"#,
+        "true\n"
+    );
+
+    #[test]
+    fn dbg_writes_to_error_writer() {
+        let tokens: std::result::Result<Vec<_>, _> =
+            crate::lexer::Lexer::new(r#"var y = dbg(1 + 2); print y; dbg("lox");"#.chars())
+                .unwrap()
+                .collect();
+        let mut stmts = crate::parser::Parser::new(tokens.unwrap().into_iter())
+            .program()
+            .unwrap();
+        let mut interpreter = crate::Interpreter::new(Vec::new()).with_error_writer(Vec::new());
+        crate::Resolver::new()
+            .resolve(&mut stmts, &mut interpreter)
+            .and_then(|_| interpreter.run_many(&stmts))
+            .unwrap();
+        assert_eq!(String::from_utf8(interpreter.writer).unwrap(), "3\n");
+        assert_eq!(
+            String::from_utf8(interpreter.error_writer).unwrap(),
+            "number: 3\nstring: \"lox\"\n"
+        );
+    }
+
+    test_interpret_ok!(
+        write_to_missing_directory,
+        r#"print write("/nonexistent/lox/out.txt", "x");"#,
+        "false\n"
+    );
+}
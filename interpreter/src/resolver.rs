@@ -1,11 +1,14 @@
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::io::Write;
 
 use crate::anyhow;
 use crate::ast::*;
+use crate::Diagnostic;
 use crate::ErrorOrCtxJmp;
 use crate::Interpreter;
 use crate::Result;
+use crate::Severity;
 
 pub type ResolveResult = Result<()>;
 
@@ -36,11 +39,22 @@ enum LoopType {
     InLoop,
 }
 
+#[derive(Debug, Clone)]
 pub struct Resolver {
     scopes: Vec<HashMap<String, VariableState>>,
+    /// Parameter positions of the function each scope belongs to, parallel to `scopes`.
+    param_slots: Vec<HashMap<String, u32>>,
+    /// Where each name in the scope at the same position in `scopes` was declared.
+    declared_at: Vec<HashMap<String, lexer::Span>>,
+    /// Declaration of the variable used at each resolved use site.
+    definitions: HashMap<lexer::Span, lexer::Span>,
     current_function: FunctionType,
     current_class: ClassType,
     current_loop: LoopType,
+    no_shadowing: bool,
+    lint_conditions: bool,
+    lint_unused_expressions: bool,
+    warnings: Vec<Diagnostic>,
 }
 
 impl Default for Resolver {
@@ -53,19 +67,71 @@ impl Resolver {
     pub fn new() -> Self {
         Self {
             scopes: vec![HashMap::new()],
+            param_slots: vec![HashMap::new()],
+            declared_at: vec![HashMap::new()],
+            definitions: HashMap::new(),
             current_function: FunctionType::None,
             current_class: ClassType::None,
             current_loop: LoopType::None,
+            no_shadowing: false,
+            lint_conditions: false,
+            lint_unused_expressions: false,
+            warnings: Vec::new(),
         }
     }
 
-    pub fn resolve_stmt<W: Write>(
+    /// Also reject a `var` that shadows a variable from an enclosing scope.
+    #[inline(always)]
+    pub fn with_no_shadowing(mut self) -> Self {
+        self.no_shadowing = true;
+        self
+    }
+
+    /// Warn about `if` and loop conditions that are assignments, as in `if (a = b)`.
+    #[inline(always)]
+    pub fn with_condition_lint(mut self) -> Self {
+        self.lint_conditions = true;
+        self
+    }
+
+    /// Warn about expression statements without side effects, like `1 + 2;`, whose value
+    /// is thrown away.
+    #[inline(always)]
+    pub fn with_unused_expression_lint(mut self) -> Self {
+        self.lint_unused_expressions = true;
+        self
+    }
+
+    /// Where the variable used at `use_span` was declared, for a use the resolver has
+    /// seen. Natives and other names defined outside the program have no declaration.
+    pub fn definition_of(&self, use_span: lexer::Span) -> Option<lexer::Span> {
+        self.definitions.get(&use_span).copied()
+    }
+
+    /// Warnings collected so far, which display like `[line 1] Warning at 'a': ...`.
+    pub fn warnings(&self) -> &[Diagnostic] {
+        &self.warnings
+    }
+
+    fn warn(&mut self, message: String, span: Option<lexer::Span>) {
+        self.warnings.push(Diagnostic {
+            severity: Severity::Warning,
+            message,
+            span,
+        });
+    }
+
+    pub fn resolve_stmt<W: Write, E: Write>(
         &mut self,
         stmt: &mut Stmt,
-        interpreter: &mut Interpreter<W>,
+        interpreter: &mut Interpreter<W, E>,
     ) -> ResolveResult {
         match stmt {
-            Stmt::Print(e) | Stmt::Expr(e) => self.resolve_expr(e, interpreter)?,
+            Stmt::Print(e) => self.resolve_expr(e, interpreter)?,
+            Stmt::Expr(e) => {
+                self.lint_unused_expression(e);
+                self.resolve_expr(e, interpreter)?
+            }
             Stmt::VariableDecl(VariableDecl { name, definition }) => {
                 self.declare(name)?;
                 match definition {
@@ -88,19 +154,38 @@ impl Resolver {
                 if_branch,
                 else_branch,
             }) => {
+                self.lint_condition(cond);
                 self.resolve_expr(cond, interpreter)?;
                 self.resolve_stmt(if_branch, interpreter)?;
                 if let Some(else_branch) = else_branch {
                     self.resolve_stmt(else_branch, interpreter)?;
                 }
             }
-            Stmt::Loop(Loop { cond, body }) => {
+            Stmt::Loop(Loop { cond, body, update }) => {
                 let previous_loop = self.current_loop;
                 self.current_loop = LoopType::InLoop;
+                self.lint_condition(cond);
                 self.resolve_expr(cond, interpreter)?;
                 self.resolve_stmt(body, interpreter)?;
+                if let Some(update) = update {
+                    self.resolve_expr(update, interpreter)?;
+                }
                 self.current_loop = previous_loop;
             }
+            Stmt::ForEach(ForEach {
+                name,
+                iterable,
+                body,
+            }) => {
+                self.resolve_expr(iterable, interpreter)?;
+                self.begin_scope();
+                self.init(name);
+                let previous_loop = self.current_loop;
+                self.current_loop = LoopType::InLoop;
+                self.resolve_stmt(body, interpreter)?;
+                self.current_loop = previous_loop;
+                self.end_scope();
+            }
             Stmt::FunctionDecl(f) => {
                 self.init(&f.name);
                 self.resolve_function(
@@ -110,17 +195,19 @@ impl Resolver {
                     interpreter,
                 )?;
             }
-            Stmt::Return(expr) => {
+            Stmt::Return(expr, span) => {
                 if self.current_function == FunctionType::None {
                     return Err(ErrorOrCtxJmp::Error(anyhow!(
                         "Error at 'return': Can't return from top-level code."
-                    )));
+                    ))
+                    .at(*span));
                 }
 
                 if self.current_function == FunctionType::Initializer && expr != &Expr::Nil {
                     return Err(ErrorOrCtxJmp::Error(anyhow!(
                         "Error at 'return': Can't return a value from an initializer."
-                    )));
+                    ))
+                    .at(*span));
                 }
 
                 self.resolve_expr(expr, interpreter)?;
@@ -130,6 +217,17 @@ impl Resolver {
                 super_class,
                 methods,
             }) => {
+                let mut seen = HashSet::new();
+                for method in methods.iter() {
+                    if !seen.insert(&method.name.token.lexeme) {
+                        return Err(ErrorOrCtxJmp::Error(anyhow!(
+                            "Error at '{}': Duplicate method name in class.",
+                            method.name
+                        ))
+                        .at(method.name.token.span));
+                    }
+                }
+
                 self.init(name);
                 let enclosing_class = self.current_class;
                 self.current_class = ClassType::Class;
@@ -140,7 +238,8 @@ impl Resolver {
                             return Err(ErrorOrCtxJmp::Error(anyhow!(
                                 "Error at '{}': A class can't inherit from itself.",
                                 name
-                            )));
+                            ))
+                            .at(sc.token.span));
                         }
                     }
                     self.resolve_expr(super_class, interpreter)?;
@@ -177,21 +276,53 @@ impl Resolver {
 
                 self.current_class = enclosing_class;
             }
-            Stmt::Break => {
+            Stmt::Break(span) => {
                 if self.current_loop == LoopType::None {
                     return Err(ErrorOrCtxJmp::Error(anyhow!(
                         "Error at 'break': Can't break from top-level code."
-                    )));
+                    ))
+                    .at(*span));
+                }
+            }
+            Stmt::Continue(span) => {
+                if self.current_loop == LoopType::None {
+                    return Err(ErrorOrCtxJmp::Error(anyhow!(
+                        "Error at 'continue': Can't continue from top-level code."
+                    ))
+                    .at(*span));
+                }
+            }
+            Stmt::Import(Import { path, alias, span }) => {
+                if self.scopes.len() > 1 {
+                    return Err(ErrorOrCtxJmp::Error(anyhow!(
+                        "Error at 'import': Can only import at top level."
+                    ))
+                    .at(*span));
+                }
+                if let Some(alias) = alias {
+                    self.declare(alias)?;
+                    self.init(alias);
+                    return Ok(());
+                }
+                // The file only runs later, so make its top-level names known now for
+                // the code after the import to resolve.
+                for stmt in interpreter.parse_import(path)? {
+                    match stmt {
+                        Stmt::VariableDecl(VariableDecl { name, .. })
+                        | Stmt::FunctionDecl(FunctionDecl { name, .. })
+                        | Stmt::ClassDecl(ClassDecl { name, .. }) => self.init(name),
+                        _ => {}
+                    }
                 }
             }
         }
         Ok(())
     }
 
-    pub fn resolve_expr<W: Write>(
+    pub fn resolve_expr<W: Write, E: Write>(
         &mut self,
         expr: &mut Expr,
-        interpreter: &mut Interpreter<W>,
+        interpreter: &mut Interpreter<W, E>,
     ) -> ResolveResult {
         match expr {
             Expr::Nil | Expr::Int(_) | Expr::Float(_) | Expr::Boolean(_) | Expr::String(_) => {}
@@ -202,40 +333,43 @@ impl Resolver {
                             return Err(ErrorOrCtxJmp::Error(anyhow!(
                                 "Error at '{}': Can't read local variable in its own initializer.",
                                 &id.token.lexeme
-                            )))
+                            ))
+                            .at(id.token.span))
                         }
                         _ => {}
                     };
                 }
                 self.resolve_local(id, interpreter, true)?
             }
-            Expr::Unary(_, e) => {
+            Expr::Unary(_, e, _) | Expr::Interpolation(e) => {
                 self.resolve_expr(e, interpreter)?;
             }
-            Expr::Binary(_, e1, e2) | Expr::Logical(_, e1, e2) => {
+            Expr::Binary(_, e1, e2, _) | Expr::Logical(_, e1, e2, _) => {
                 self.resolve_expr(e1, interpreter)?;
                 self.resolve_expr(e2, interpreter)?;
             }
-            Expr::Assign(ident, e) => {
+            Expr::Assign(ident, e, span) => {
                 self.resolve_expr(e, interpreter)?;
-                if let Expr::Ident(ref mut id) = **ident {
-                    self.resolve_local(id, interpreter, false)?;
-                } else {
-                    return Err(ErrorOrCtxJmp::Error(anyhow!(
-                        "Error at '=': Invalid assignment target."
-                    )));
-                };
+                match **ident {
+                    Expr::Ident(ref mut id) => self.resolve_local(id, interpreter, false)?,
+                    _ => {
+                        return Err(ErrorOrCtxJmp::Error(anyhow!(
+                            "Error at '=': Invalid assignment target."
+                        ))
+                        .at(*span))
+                    }
+                }
             }
-            Expr::Call(callee, args) => {
+            Expr::Call(callee, args, _) => {
                 self.resolve_expr(callee, interpreter)?;
                 for arg in args {
                     self.resolve_expr(&mut arg.value, interpreter)?;
                 }
             }
-            Expr::Lambda(params, body) => {
+            Expr::Lambda(params, _, body) => {
                 self.resolve_function(params, body, FunctionType::Function, interpreter)?
             }
-            Expr::Get(object, _fields) => {
+            Expr::Get(object, _fields) | Expr::GetSafe(object, _fields) => {
                 self.resolve_expr(object, interpreter)?;
             }
             Expr::Set(object, _, value) => {
@@ -246,7 +380,8 @@ impl Resolver {
                 if self.current_class == ClassType::None {
                     return Err(ErrorOrCtxJmp::Error(anyhow!(
                         "Error at 'this': Can't use 'this' outside of a class."
-                    )));
+                    ))
+                    .at(this.token.span));
                 }
                 self.resolve_local(this, interpreter, false)?
             }
@@ -254,7 +389,8 @@ impl Resolver {
                 if self.current_class == ClassType::None {
                     return Err(ErrorOrCtxJmp::Error(anyhow!(
                         "Error at 'super': Can't use 'super' outside of a class."
-                    )));
+                    ))
+                    .at(super_class.token.span));
                 }
                 self.resolve_local(super_class, interpreter, false)?;
             }
@@ -262,21 +398,46 @@ impl Resolver {
         Ok(())
     }
 
-    pub fn resolve<W: Write>(
+    pub fn resolve<W: Write, E: Write>(
         &mut self,
         stmts: &mut [Stmt],
-        interpreter: &mut Interpreter<W>,
+        interpreter: &mut Interpreter<W, E>,
     ) -> ResolveResult {
+        if self.scopes.len() == 1 {
+            self.hoist_functions(stmts);
+        }
         for stmt in stmts {
             self.resolve_stmt(stmt, interpreter)?;
         }
         Ok(())
     }
 
-    pub fn resolve_local<W: Write>(
+    /// Declares top-level function names up front so global functions can call each
+    /// other regardless of declaration order. Local functions are not hoisted: that
+    /// would let a local `fun` capture references made earlier in its block to a global
+    /// of the same name. Names also declared with `var` keep the usual declare-before-use
+    /// rules.
+    fn hoist_functions(&mut self, stmts: &[Stmt]) {
+        let vars: HashSet<_> = stmts
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Stmt::VariableDecl(VariableDecl { name, .. }) => Some(&name.token.lexeme),
+                _ => None,
+            })
+            .collect();
+        for stmt in stmts {
+            if let Stmt::FunctionDecl(f) = stmt {
+                if !vars.contains(&f.name.token.lexeme) {
+                    self.init(&f.name);
+                }
+            }
+        }
+    }
+
+    pub fn resolve_local<W: Write, E: Write>(
         &mut self,
         id: &mut Identifier,
-        interpreter: &mut Interpreter<W>,
+        interpreter: &mut Interpreter<W, E>,
         check_initialized: bool,
     ) -> ResolveResult {
         for (i, scope) in self.scopes.iter_mut().rev().enumerate() {
@@ -285,11 +446,17 @@ impl Resolver {
                     return Err(ErrorOrCtxJmp::Error(anyhow!(
                         "Error at '{0}': Accessed an unintialized variable '{0}'.",
                         &id.token.lexeme
-                    )))
+                    ))
+                    .at(id.token.span))
                 }
                 Some(b) => {
                     *b = VariableState::Initialized;
                     interpreter.resolve(id, i);
+                    let depth = self.scopes.len() - 1 - i;
+                    id.slot = self.param_slots[depth].get(&id.token.lexeme).copied();
+                    if let Some(at) = self.declared_at[depth].get(&id.token.lexeme) {
+                        self.definitions.insert(id.token.span, *at);
+                    }
                     return Ok(());
                 }
                 None => {
@@ -298,58 +465,119 @@ impl Resolver {
             }
         }
 
+        if interpreter.is_global(&id.token.lexeme) {
+            interpreter.resolve(id, self.scopes.len() - 1);
+            return Ok(());
+        }
+
         if id.token.lexeme == "super" {
-            return Err(ErrorOrCtxJmp::Error(anyhow!(
+            Err(ErrorOrCtxJmp::Error(anyhow!(
                 "Error at 'super': Can't use 'super' in a class with no superclass."
-            )));
+            ))
+            .at(id.token.span))
         } else {
-            return Err(ErrorOrCtxJmp::Error(anyhow!(
-                "Undefined variable '{}'.",
-                id.token.lexeme
-            )));
+            Err(
+                ErrorOrCtxJmp::Error(anyhow!("Undefined variable '{}'.", id.token.lexeme))
+                    .at(id.token.span),
+            )
         }
     }
 
-    fn resolve_function<W: Write>(
+    fn resolve_function<W: Write, E: Write>(
         &mut self,
         params: &mut [Identifier],
         body: &mut [Stmt],
         ftype: FunctionType,
-        interpreter: &mut Interpreter<W>,
+        interpreter: &mut Interpreter<W, E>,
     ) -> ResolveResult {
         let enclosing_function = self.current_function;
+        let enclosing_loop = self.current_loop;
         self.current_function = ftype;
+        self.current_loop = LoopType::None;
         self.begin_scope();
 
-        for param in params {
+        for (slot, param) in params.iter().enumerate() {
             self.init(param);
+            if let Some(slots) = self.param_slots.last_mut() {
+                slots.insert(param.token.lexeme.clone(), slot as u32);
+            }
         }
 
         self.resolve(body, interpreter)?;
 
         self.end_scope();
         self.current_function = enclosing_function;
+        self.current_loop = enclosing_loop;
         Ok(())
     }
 
+    fn lint_condition(&mut self, cond: &Expr) {
+        if let (true, Expr::Assign(target, ..)) = (self.lint_conditions, cond) {
+            if let Expr::Ident(id) = target.as_ref() {
+                self.warn(
+                    format!(
+                        "Warning at '{}': Possible unintended assignment in condition.",
+                        id.token.lexeme
+                    ),
+                    Some(id.token.span),
+                );
+            }
+        }
+    }
+
+    fn lint_unused_expression(&mut self, expr: &Expr) {
+        if !self.lint_unused_expressions {
+            return;
+        }
+        let mut effects = SideEffects::default();
+        effects.visit_expr(expr);
+        if !effects.found {
+            self.warn(
+                "Warning: Expression statement has no effect.".into(),
+                effects.span,
+            );
+        }
+    }
+
     fn begin_scope(&mut self) {
         self.scopes.push(HashMap::new());
+        self.param_slots.push(HashMap::new());
+        self.declared_at.push(HashMap::new());
     }
 
     fn end_scope(&mut self) {
         self.scopes.pop();
+        self.param_slots.pop();
+        self.declared_at.pop();
     }
 
     fn declare(&mut self, name: &Identifier) -> Result<()> {
+        if self.no_shadowing {
+            let enclosing = &self.scopes[..self.scopes.len().saturating_sub(1)];
+            if enclosing
+                .iter()
+                .any(|scope| scope.contains_key(&name.token.lexeme))
+            {
+                return Err(ErrorOrCtxJmp::Error(anyhow!(
+                    "Error at '{}': Variable shadows a variable in an enclosing scope.",
+                    name.token.lexeme
+                ))
+                .at(name.token.span));
+            }
+        }
         if let Some(scope) = self.scopes.last_mut() {
             if scope.contains_key(&name.token.lexeme) {
                 return Err(ErrorOrCtxJmp::Error(anyhow!(
                     "Error at '{}': Already a variable with this name in this scope.",
                     name.token.lexeme
-                )));
+                ))
+                .at(name.token.span));
             }
             scope.insert(name.token.lexeme.clone(), VariableState::Declared);
         }
+        if let Some(at) = self.declared_at.last_mut() {
+            at.insert(name.token.lexeme.clone(), name.token.span);
+        }
         Ok(())
     }
 
@@ -363,5 +591,192 @@ impl Resolver {
         if let Some(scope) = self.scopes.last_mut() {
             scope.insert(name.token.lexeme.clone(), VariableState::Initialized);
         }
+        if let Some(at) = self.declared_at.last_mut() {
+            at.insert(name.token.lexeme.clone(), name.token.span);
+        }
+    }
+}
+
+/// Looks for calls and assignments in an expression, and the first position in it.
+#[derive(Default)]
+struct SideEffects {
+    found: bool,
+    span: Option<lexer::Span>,
+}
+
+impl ExprVisitor for SideEffects {
+    fn visit_expr(&mut self, expr: &Expr) {
+        let span = match expr {
+            Expr::Call(..) | Expr::Assign(..) | Expr::Set(..) => {
+                self.found = true;
+                return;
+            }
+            Expr::Unary(_, _, span)
+            | Expr::Binary(_, _, _, span)
+            | Expr::Logical(_, _, _, span) => Some(*span),
+            Expr::Ident(id) | Expr::This(id) | Expr::Get(_, id) | Expr::GetSafe(_, id) => {
+                Some(id.token.span)
+            }
+            _ => None,
+        };
+        self.span = self.span.or(span);
+        walk_expr(self, expr)
+    }
+
+    // Creating a function runs none of its body.
+    fn visit_lambda_body(&mut self, _body: &[Stmt]) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::sink;
+
+    use super::*;
+
+    fn resolve(input: &str, mut resolver: Resolver) -> Result<Resolver> {
+        let tokens: std::result::Result<Vec<_>, _> =
+            crate::lexer::Lexer::new(input.chars()).unwrap().collect();
+        let mut stmts = crate::parser::Parser::new(tokens.unwrap().into_iter())
+            .program()
+            .unwrap();
+        resolver
+            .resolve(&mut stmts, &mut Interpreter::new(sink()))
+            .map(|_| resolver)
+    }
+
+    #[test]
+    fn same_scope_redeclaration_is_always_an_error() {
+        for resolver in [Resolver::new(), Resolver::new().with_no_shadowing()] {
+            let e = resolve("{ var a = 1; var a = 2; }", resolver).unwrap_err();
+            assert_eq!(
+                e.to_string(),
+                "Error at 'a': Already a variable with this name in this scope."
+            );
+        }
+    }
+
+    #[test]
+    fn definition_of_uses() {
+        let input = "var a = 1;\n{\n  var a = 2;\n  print a;\n}\nfun f(b) {\n  return a + b;\n}";
+        let resolver = resolve(input, Resolver::new()).unwrap();
+
+        let span = lexer::Span::new;
+        assert_eq!(resolver.definition_of(span(4, 9)), Some(span(3, 7)));
+        assert_eq!(resolver.definition_of(span(7, 10)), Some(span(1, 5)));
+        assert_eq!(resolver.definition_of(span(7, 14)), Some(span(6, 7)));
+        assert_eq!(resolver.definition_of(span(1, 5)), None);
+    }
+
+    #[test]
+    fn assign_to_this_is_invalid() {
+        let e = resolve("class A {\n  f() {\n    this = 1;\n  }\n}", Resolver::new()).unwrap_err();
+        assert_eq!(e.to_string(), "Error at '=': Invalid assignment target.");
+        assert_eq!(e.span().map(|span| span.line), Some(3));
+        assert!(resolve(
+            "class A { f() { var a = 1; a = this; this.a = a; } }",
+            Resolver::new()
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn this_in_lambda_needs_enclosing_class() {
+        let input = "class A { f() { return fun () { return this; }; } }";
+        assert!(resolve(input, Resolver::new()).is_ok());
+        let e = resolve("var f = fun () { return this; };", Resolver::new()).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Error at 'this': Can't use 'this' outside of a class."
+        );
+    }
+
+    #[test]
+    fn enclosing_scope_shadowing_is_opt_in() {
+        let input = "var a = 1; { var b = 2; { var a = 3; } }";
+        assert!(resolve(input, Resolver::new()).is_ok());
+        let e = resolve(input, Resolver::new().with_no_shadowing()).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Error at 'a': Variable shadows a variable in an enclosing scope."
+        );
+        assert!(resolve(
+            "{ var a = 1; } { var a = 2; }",
+            Resolver::new().with_no_shadowing()
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn continue_outside_loop() {
+        for input in ["continue;", "while (true) { fun f() { continue; } }"] {
+            let e = resolve(input, Resolver::new()).unwrap_err();
+            assert_eq!(
+                e.to_string(),
+                "Error at 'continue': Can't continue from top-level code."
+            );
+        }
+    }
+
+    #[test]
+    fn break_does_not_cross_function_boundary() {
+        for input in [
+            "while (true) { fun f() { break; } }",
+            "while (true) { var f = fun () { break; }; }",
+        ] {
+            let e = resolve(input, Resolver::new()).unwrap_err();
+            assert_eq!(
+                e.to_string(),
+                "Error at 'break': Can't break from top-level code."
+            );
+        }
+        assert!(resolve(
+            "while (true) { fun f() { while (true) { break; } } break; }",
+            Resolver::new()
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn method_refers_to_later_method() {
+        let input = "class A { method0() { return this.method1(); } method1() { return 1; } }";
+        assert!(resolve(input, Resolver::new()).is_ok());
+    }
+
+    #[test]
+    fn assignment_in_condition_lint() {
+        let input = "var a = 1;\nif (a = 2) print a;\nif (a == 2) print a;\nwhile (a = nil) {}";
+        let resolver = resolve(input, Resolver::new().with_condition_lint()).unwrap();
+        assert_eq!(
+            resolver
+                .warnings()
+                .iter()
+                .map(Diagnostic::to_string)
+                .collect::<Vec<_>>(),
+            [
+                "[line 2] Warning at 'a': Possible unintended assignment in condition.",
+                "[line 4] Warning at 'a': Possible unintended assignment in condition.",
+            ]
+        );
+
+        let resolver = resolve(input, Resolver::new()).unwrap();
+        assert!(resolver.warnings().is_empty());
+    }
+
+    #[test]
+    fn unused_expression_lint() {
+        let input = "var x;\nvar y = 2;\nfun f() {}\nf();\nx = 1;\n1 + 2;\nx == y;\nnil;\nx.a = f;";
+        let resolver = resolve(input, Resolver::new().with_unused_expression_lint()).unwrap();
+        assert_eq!(
+            resolver
+                .warnings()
+                .iter()
+                .map(Diagnostic::to_string)
+                .collect::<Vec<_>>(),
+            [
+                "[line 6] Warning: Expression statement has no effect.",
+                "[line 7] Warning: Expression statement has no effect.",
+                "Warning: Expression statement has no effect.",
+            ]
+        );
     }
 }
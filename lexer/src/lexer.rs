@@ -12,6 +12,9 @@ use crate::KEYWORDS;
 pub struct Lexer<I: Iterator> {
     input: PeekMoreIterator<I>,
     span: Span,
+    /// Brace depth inside each `${` currently being lexed, innermost last.
+    interpolations: Vec<usize>,
+    preserve_comments: bool,
 }
 
 impl<I: Iterator<Item = char>> Lexer<I> {
@@ -19,11 +22,28 @@ impl<I: Iterator<Item = char>> Lexer<I> {
         let lexer = Lexer {
             input: input.peekmore(),
             span: Span::new(1, 1),
+            interpolations: Vec::new(),
+            preserve_comments: false,
         };
 
         Ok(lexer)
     }
 
+    /// Like `new`, but emits `LineComment` and `BlockComment` tokens holding the full
+    /// comment text instead of skipping comments. Meant for formatters; the parser does
+    /// not accept these tokens.
+    pub fn new_preserving_comments(input: I) -> Result<Self> {
+        let mut lexer = Self::new(input)?;
+        lexer.preserve_comments = true;
+        Ok(lexer)
+    }
+
+    /// Position of the next character to be lexed.
+    #[inline(always)]
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
     #[inline(always)]
     fn eof(&mut self) -> bool {
         self.input.peek().is_none()
@@ -68,12 +88,14 @@ impl<I: Iterator<Item = char>> Lexer<I> {
     }
 
     #[inline(always)]
-    fn skip_while<F>(&mut self, f: F)
+    fn take_while<F>(&mut self, f: F) -> Vec<char>
     where
         F: Fn(char) -> bool,
     {
+        let mut taken = Vec::new();
         while let Some(ch) = self.input.peek() {
             if f(*ch) {
+                taken.push(*ch);
                 let t = self.input.next();
                 if let Some('\n') = t {
                     self.span.newline();
@@ -82,26 +104,89 @@ impl<I: Iterator<Item = char>> Lexer<I> {
                 break;
             }
         }
+        taken
     }
 
-    #[inline(always)]
-    fn take_while<F>(&mut self, f: F) -> Vec<char>
-    where
-        F: Fn(char) -> bool,
-    {
-        let mut taken = Vec::new();
-        while let Some(ch) = self.input.peek() {
-            if f(*ch) {
-                taken.push(*ch);
-                let t = self.input.next();
-                if let Some('\n') = t {
-                    self.span.newline();
+    /// Scans a string body after its opening delimiter (`"`, or the `}` closing an
+    /// interpolated expression), which is `open` chars wide. A `${` ends the fragment
+    /// with an `Interpolation` token and the embedded expression is lexed next; `\${`
+    /// is a literal `${`.
+    fn string(&mut self, open: usize) -> Result<Token> {
+        let mut literal = String::new();
+        let mut escapes = 0;
+        loop {
+            match self.input.next() {
+                Some('"') => {
+                    let token = self.make_token_with_lexeme(TokenType::Str, literal);
+                    self.span.advance_col(open + escapes + 1);
+                    return token;
                 }
-            } else {
-                break;
+                Some('$') if self.match_next('{') => {
+                    self.interpolations.push(0);
+                    let token = self.make_token_with_lexeme(TokenType::Interpolation, literal);
+                    self.span.advance_col(open + escapes + 2);
+                    return token;
+                }
+                Some('\\')
+                    if self.match_nth(0, |c| c == '$') && self.match_nth(1, |c| c == '{') =>
+                {
+                    self.skip(2);
+                    literal.push_str("${");
+                    escapes += 1;
+                }
+                Some(c) => {
+                    if c == '\n' {
+                        self.span.newline();
+                    }
+                    literal.push(c);
+                }
+                None => return Err(LexerErrorKind::UnterminatedStringLiteral),
+            }
+        }
+    }
+
+    /// Scans `r"..."` or `r#"..."#` after the `r`, keeping the contents verbatim. The
+    /// closing quote must be followed by as many `#` as the opening one.
+    fn raw_string(&mut self) -> Result<Token> {
+        let hashes = self.take_while(|c| c == '#').len();
+        if !self.match_next('"') {
+            return Err(LexerErrorKind::InvalidRawString);
+        }
+        let mut literal = String::new();
+        loop {
+            match self.input.next() {
+                Some('"') if (0..hashes).all(|i| self.match_nth(i, |c| c == '#')) => {
+                    self.skip(hashes);
+                    break;
+                }
+                Some(c) => {
+                    if c == '\n' {
+                        self.span.newline();
+                    }
+                    literal.push(c);
+                }
+                None => return Err(LexerErrorKind::UnterminatedStringLiteral),
+            }
+        }
+        let token = self.make_token_with_lexeme(TokenType::Str, literal);
+        self.span.advance_col(3 + 2 * hashes);
+        token
+    }
+
+    /// Consumes a block comment after its opening `/*` and returns its full text.
+    fn block_comment(&mut self) -> Result<String> {
+        let mut text = String::from("/*");
+        loop {
+            text.extend(self.take_while(|c| c != '*'));
+            text.extend(self.input.next());
+            if self.eof() {
+                return Err(LexerErrorKind::UntermiatedBlockComment);
+            }
+            if self.match_next('/') {
+                text.push('/');
+                return Ok(text);
             }
         }
-        taken
     }
 
     #[inline(always)]
@@ -112,7 +197,7 @@ impl<I: Iterator<Item = char>> Lexer<I> {
 
     #[inline(always)]
     fn make_token_with_lexeme(&mut self, ty: TokenType, lexeme: String) -> Result<Token> {
-        let len = lexeme.len();
+        let len = lexeme.chars().count();
         let token = Ok(Token {
             ty,
             lexeme,
@@ -130,33 +215,51 @@ impl<I: Iterator<Item = char>> Lexer<I> {
                 Some(c) => match c {
                     '(' => return self.make_token(LeftParen),
                     ')' => return self.make_token(RightParen),
-                    '{' => return self.make_token(LeftBrace),
-                    '}' => return self.make_token(RightBrace),
+                    '{' => {
+                        if let Some(depth) = self.interpolations.last_mut() {
+                            *depth += 1;
+                        }
+                        return self.make_token(LeftBrace);
+                    }
+                    '}' => match self.interpolations.last_mut() {
+                        Some(0) => {
+                            self.interpolations.pop();
+                            return self.string(1);
+                        }
+                        Some(depth) => {
+                            *depth -= 1;
+                            return self.make_token(RightBrace);
+                        }
+                        None => return self.make_token(RightBrace),
+                    },
                     '.' => return self.make_token(Dot),
                     ',' => return self.make_token(Comma),
                     '+' => return self.make_token(Plus),
                     '-' => return self.make_token(Minus),
                     ';' => return self.make_token(SemiColon),
+                    ':' => return self.make_token(Colon),
                     '*' => return self.make_token(Star),
-                    '/' => match self.match_next('/') {
-                        true => {
-                            self.skip_while(|c| c != '\n');
-                            continue;
+                    '/' => {
+                        let span = self.span;
+                        let (ty, lexeme) = if self.match_next('/') {
+                            let text: String = self.take_while(|c| c != '\n').into_iter().collect();
+                            (LineComment, format!("//{}", text))
+                        } else if self.match_next('*') {
+                            (BlockComment, self.block_comment()?)
+                        } else {
+                            return self.make_token(ForwardSlash);
+                        };
+                        if self.preserve_comments {
+                            return Ok(Token { ty, lexeme, span });
                         }
-                        false => match self.match_next('*') {
-                            true => loop {
-                                self.skip_while(|c| c != '*');
-                                self.skip(1);
-                                if self.eof() {
-                                    return Err(LexerErrorKind::UntermiatedBlockComment);
-                                }
-                                if self.match_next('/') {
-                                    break;
-                                }
-                            },
-                            false => return self.make_token(ForwardSlash),
-                        },
-                    },
+                        continue;
+                    }
+                    '?' => {
+                        return match self.match_next('.') {
+                            true => self.make_token(QuestionDot),
+                            false => Err(LexerErrorKind::UnexpectedChar { ch: '?' }),
+                        }
+                    }
                     '!' => {
                         return match self.match_next('=') {
                             true => self.make_token(Ne),
@@ -189,17 +292,28 @@ impl<I: Iterator<Item = char>> Lexer<I> {
                         self.span.newline();
                         continue;
                     }
-                    '"' => {
-                        let literal: String = self.take_while(|c| c != '"').into_iter().collect();
-                        if !self.match_nth(0, |c| c == '"') {
-                            return Err(LexerErrorKind::UnterminatedStringLiteral);
+                    '"' => return self.string(1),
+                    'r' if self.match_nth(0, |c| c == '"' || c == '#') => {
+                        return self.raw_string();
+                    }
+                    '0' if self
+                        .match_nth(0, |c| matches!(c, 'x' | 'X' | 'b' | 'B' | 'o' | 'O')) =>
+                    {
+                        let prefix = self.input.next().expect("BUG");
+                        let radix = match prefix {
+                            'x' | 'X' => 16,
+                            'b' | 'B' => 2,
+                            _ => 8,
+                        };
+                        let mut number = vec![c, prefix];
+                        let digits = self.take_while(|c| c.is_digit(radix));
+                        if digits.is_empty() {
+                            return Err(LexerErrorKind::InvalidNumericLiteral {
+                                literal: number.into_iter().collect(),
+                            });
                         }
-                        self.skip(1);
-                        // For starting and ending double quotes as literl only contains unquoted
-                        // string.
-                        let token = self.make_token_with_lexeme(Str, literal);
-                        self.span.advance_col(2);
-                        return token;
+                        number.extend(digits);
+                        return self.make_token_with_lexeme(Numeric, number.into_iter().collect());
                     }
                     d if d.is_ascii_digit() => {
                         let mut number = vec![c];
@@ -224,6 +338,10 @@ impl<I: Iterator<Item = char>> Lexer<I> {
                         return Err(LexerErrorKind::UnexpectedChar { ch });
                     }
                 },
+                None if !self.interpolations.is_empty() => {
+                    self.interpolations.clear();
+                    return Err(LexerErrorKind::UnterminatedStringLiteral);
+                }
                 None => return self.make_token(Eof),
             };
         }
@@ -336,6 +454,15 @@ mod tests {
         Token::new(Dot, Span::new(1, 13)),
     );
 
+    test_lexer_ok!(
+        columns_count_chars,
+        r#""ॐ¶"; "ab";"#,
+        Token::new_with_lexeme(Str, "ॐ¶", Span::new(1, 1)),
+        Token::new(SemiColon, Span::new(1, 5)),
+        Token::new_with_lexeme(Str, "ab", Span::new(1, 7)),
+        Token::new(SemiColon, Span::new(1, 11))
+    );
+
     test_lexer_ok!(
         ignore_single_line_comment,
         "//Comment to be ignored.\n {}",
@@ -356,6 +483,35 @@ mod tests {
         Token::new(SemiColon, Span::new(7, 1))
     );
 
+    #[test]
+    fn preserve_comments() {
+        use TokenType::*;
+        let input = "// first\nvar a; /* second\n */ a // third";
+        let tokens: Result<Vec<Token>> = Lexer::new_preserving_comments(input.chars())
+            .unwrap()
+            .collect();
+        let tokens: Vec<_> = tokens
+            .unwrap()
+            .into_iter()
+            .map(|t| (t.ty, t.lexeme))
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                (LineComment, "// first".into()),
+                (Var, "var".into()),
+                (Ident, "a".into()),
+                (SemiColon, ";".into()),
+                (BlockComment, "/* second\n */".into()),
+                (Ident, "a".into()),
+                (LineComment, "// third".into()),
+            ]
+        );
+
+        let tokens: Result<Vec<Token>> = Lexer::new(input.chars()).unwrap().collect();
+        assert_eq!(tokens.unwrap().len(), 4);
+    }
+
     test_lexer_ok!(
         literal_str,
         "\"This is a string followed by a semi-colon.\";",
@@ -376,6 +532,85 @@ mod tests {
         Token::new(SemiColon, Span::new(1, 9))
     );
 
+    test_lexer_ok!(
+        trailing_decimal_point,
+        "12.",
+        Token::new_with_lexeme(Numeric, "12", Span::new(1, 1)),
+        Token::new(Dot, Span::new(1, 3))
+    );
+
+    test_lexer_ok!(
+        number_then_property,
+        "3.foo",
+        Token::new_with_lexeme(Numeric, "3", Span::new(1, 1)),
+        Token::new(Dot, Span::new(1, 2)),
+        Token::new_with_lexeme(Ident, "foo", Span::new(1, 3))
+    );
+
+    test_lexer_ok!(
+        number_double_dot,
+        "1..2",
+        Token::new_with_lexeme(Numeric, "1", Span::new(1, 1)),
+        Token::new(Dot, Span::new(1, 2)),
+        Token::new(Dot, Span::new(1, 3)),
+        Token::new_with_lexeme(Numeric, "2", Span::new(1, 4))
+    );
+
+    test_lexer_ok!(
+        raw_string,
+        r##"r"a\nb" + r#"say "hi""# ;"##,
+        Token::new_with_lexeme(Str, r"a\nb", Span::new(1, 1)),
+        Token::new(Plus, Span::new(1, 9)),
+        Token::new_with_lexeme(Str, r#"say "hi""#, Span::new(1, 11)),
+        Token::new(SemiColon, Span::new(1, 25))
+    );
+
+    test_lexer_ok!(
+        string_interpolation,
+        r#""sum=${a + {}}!" "$""#,
+        Token::new_with_lexeme(Interpolation, "sum=", Span::new(1, 1)),
+        Token::new_with_lexeme(Ident, "a", Span::new(1, 8)),
+        Token::new(Plus, Span::new(1, 10)),
+        Token::new(LeftBrace, Span::new(1, 12)),
+        Token::new(RightBrace, Span::new(1, 13)),
+        Token::new_with_lexeme(Str, "!", Span::new(1, 14)),
+        Token::new_with_lexeme(Str, "$", Span::new(1, 18))
+    );
+
+    test_lexer_ok!(
+        escaped_interpolation,
+        r#""cost \${x} ${y}";"#,
+        Token::new_with_lexeme(Interpolation, "cost ${x} ", Span::new(1, 1)),
+        Token::new_with_lexeme(Ident, "y", Span::new(1, 15)),
+        Token::new_with_lexeme(Str, "", Span::new(1, 16)),
+        Token::new(SemiColon, Span::new(1, 18))
+    );
+
+    test_lexer_err!(
+        unterminated_interpolation,
+        r#""${1"#,
+        LexerErrorKind::UnterminatedStringLiteral
+    );
+
+    test_lexer_ok!(
+        raw_identifier_prefix,
+        "r + rest",
+        Token::new_with_lexeme(Ident, "r", Span::new(1, 1)),
+        Token::new(Plus, Span::new(1, 3)),
+        Token::new_with_lexeme(Ident, "rest", Span::new(1, 5))
+    );
+
+    test_lexer_err!(
+        unterminated_raw_string,
+        "r#\"abc\"",
+        LexerErrorKind::UnterminatedStringLiteral
+    );
+    test_lexer_err!(
+        raw_string_without_quote,
+        "r#abc",
+        LexerErrorKind::InvalidRawString
+    );
+
     test_lexer_ok!(
         literal_float,
         "12.123123 + 345 ",
@@ -436,6 +671,40 @@ mod tests {
         Token::new(SemiColon, Span::new(1, 6))
     );
 
+    test_lexer_ok!(
+        literal_hex,
+        "0xFF + 0X10;",
+        Token::new_with_lexeme(Numeric, "0xFF", Span::new(1, 1)),
+        Token::new(Plus, Span::new(1, 6)),
+        Token::new_with_lexeme(Numeric, "0X10", Span::new(1, 8)),
+        Token::new(SemiColon, Span::new(1, 12))
+    );
+
+    test_lexer_err!(
+        hex_without_digits,
+        "0x;",
+        LexerErrorKind::InvalidNumericLiteral
+    );
+
+    test_lexer_ok!(
+        literal_bin_oct,
+        "0b1010 0o17",
+        Token::new_with_lexeme(Numeric, "0b1010", Span::new(1, 1)),
+        Token::new_with_lexeme(Numeric, "0o17", Span::new(1, 8)),
+    );
+
+    test_lexer_err!(
+        bin_without_digits,
+        "0b;",
+        LexerErrorKind::InvalidNumericLiteral
+    );
+
+    test_lexer_err!(
+        oct_without_digits,
+        "0o9",
+        LexerErrorKind::InvalidNumericLiteral
+    );
+
     test_lexer_err!(
         unterminated_string_literal,
         "\" this string is not terminated",
@@ -454,4 +723,18 @@ mod tests {
         "#,
         JLoxError::UntermiatedBlockComment
     );
+
+    test_lexer_ok!(
+        question_dot,
+        "a?.b",
+        Token::new_with_lexeme(Ident, "a", Span::new(1, 1)),
+        Token::new(QuestionDot, Span::new(1, 2)),
+        Token::new_with_lexeme(Ident, "b", Span::new(1, 4)),
+    );
+
+    test_lexer_err!(
+        lone_question_mark,
+        "a ? b",
+        LexerErrorKind::UnexpectedChar { ch: '?' }
+    );
 }
@@ -40,6 +40,8 @@ lazy_static! {
             ("var", TokenType::Var),
             ("while", TokenType::While),
             ("break", TokenType::Break),
+            ("continue", TokenType::Continue),
+            ("import", TokenType::Import),
         ]
         .into_iter()
         .collect()
@@ -56,6 +58,12 @@ pub enum LexerErrorKind {
 
     #[error("Error: Unexpected char '{ch}' found in input.")]
     UnexpectedChar { ch: char },
+
+    #[error("Error: Invalid numeric literal '{literal}'.")]
+    InvalidNumericLiteral { literal: String },
+
+    #[error("Error: Expect '\"' to start raw string.")]
+    InvalidRawString,
 }
 
 type Result<T> = std::result::Result<T, LexerErrorKind>;
@@ -1,4 +1,6 @@
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Default)]
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Hash, Default, Serialize)]
 pub struct Span {
     pub line: usize,
     pub col: usize,
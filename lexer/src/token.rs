@@ -1,8 +1,11 @@
 use std::fmt::{Debug, Display};
 
+use serde::Serialize;
+
 use crate::span::Span;
+use crate::KEYWORDS;
 
-#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Ord, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Ord, Eq, Serialize)]
 pub enum TokenType {
     // Single char tokens
     LeftParen,
@@ -14,10 +17,12 @@ pub enum TokenType {
     Plus,
     Minus,
     SemiColon,
+    Colon,
     ForwardSlash,
     Star,
 
     // Double char tokens
+    QuestionDot,
     Not,
     Ne,
     Eq,
@@ -29,6 +34,8 @@ pub enum TokenType {
 
     // Literals
     Str,
+    /// A string fragment followed by an embedded `${...}` expression.
+    Interpolation,
     Numeric,
     Ident,
     True,
@@ -51,11 +58,27 @@ pub enum TokenType {
     While,
     Break,
     Continue,
+    Import,
+
+    // Comments, only emitted by `Lexer::new_preserving_comments`
+    LineComment,
+    BlockComment,
 
     // Eof
     Eof,
 }
 
+impl TokenType {
+    /// Returns the keyword token type for `s`, if `s` is a reserved word.
+    pub fn from_keyword(s: &str) -> Option<TokenType> {
+        KEYWORDS.get(s).copied()
+    }
+
+    pub fn is_keyword(&self) -> bool {
+        KEYWORDS.values().any(|ty| ty == self)
+    }
+}
+
 impl Display for TokenType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         use TokenType::*;
@@ -72,8 +95,10 @@ impl Display for TokenType {
                 Plus => "+",
                 Minus => "-",
                 SemiColon => ";",
+                Colon => ":",
                 ForwardSlash => "/",
                 Star => "*",
+                QuestionDot => "?.",
                 Not => "!",
                 Ne => "!=",
                 Eq => "=",
@@ -88,7 +113,7 @@ impl Display for TokenType {
                 Class => "class",
                 Else => "else",
                 For => "for",
-                Fun => "fn",
+                Fun => "fun",
                 If => "if",
                 Nil => "nil",
                 Or => "or",
@@ -100,8 +125,12 @@ impl Display for TokenType {
                 While => "while",
                 Break => "break",
                 Continue => "continue",
+                Import => "import",
+                LineComment => "<line comment>",
+                BlockComment => "<block comment>",
                 Eof => "<eof>",
                 Str => "<str>",
+                Interpolation => "<interpolation>",
                 Numeric => "<numeric>",
                 Ident => "<identifier>",
             }
@@ -109,7 +138,7 @@ impl Display for TokenType {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct Token {
     pub ty: TokenType,
     pub lexeme: String,
@@ -129,8 +158,10 @@ impl Token {
             Plus => "+",
             Minus => "-",
             SemiColon => ";",
+            Colon => ":",
             ForwardSlash => "/",
             Star => "*",
+            QuestionDot => "?.",
             Not => "!",
             Ne => "!=",
             Eq => "=",
@@ -145,7 +176,7 @@ impl Token {
             Class => "class",
             Else => "else",
             For => "for",
-            Fun => "fn",
+            Fun => "fun",
             If => "if",
             Nil => "nil",
             Or => "or",
@@ -157,6 +188,7 @@ impl Token {
             While => "while",
             Break => "break",
             Continue => "continue",
+            Import => "import",
             Eof => "<eof>",
             _ => unreachable!(),
         };
@@ -174,13 +206,43 @@ impl Token {
             span,
         }
     }
+
+    pub fn is_keyword(&self) -> bool {
+        self.ty.is_keyword()
+    }
 }
 
 impl Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self.ty {
-            TokenType::Numeric | TokenType::Ident | TokenType::Str => write!(f, "{}", self.lexeme),
+            TokenType::Numeric
+            | TokenType::Ident
+            | TokenType::Str
+            | TokenType::Interpolation
+            | TokenType::LineComment
+            | TokenType::BlockComment => {
+                write!(f, "{}", self.lexeme)
+            }
             _ => write!(f, "{}", self.ty),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_keyword() {
+        assert_eq!(TokenType::from_keyword("while"), Some(TokenType::While));
+        assert_eq!(TokenType::from_keyword("foo"), None);
+    }
+
+    #[test]
+    fn is_keyword() {
+        assert!(TokenType::Class.is_keyword());
+        assert!(!TokenType::Ident.is_keyword());
+        assert!(Token::new(TokenType::Return, Span::new(1, 1)).is_keyword());
+        assert!(!Token::new_with_lexeme(TokenType::Ident, "foo", Span::new(1, 1)).is_keyword());
+    }
+}
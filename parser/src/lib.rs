@@ -2,6 +2,10 @@ extern crate lox_ast as ast;
 
 extern crate lox_lexer as lexer;
 
+extern crate peekmore;
+use peekmore::PeekMore;
+use peekmore::PeekMoreIterator;
+
 extern crate thiserror;
 use thiserror::Error;
 
@@ -22,6 +26,9 @@ pub enum ParserErrorKind {
     #[error("{0}")]
     MissingTokenWithMsg(String),
 
+    #[error("Error at end: {0}")]
+    UnexpectedEof(String),
+
     #[error("Error at '{0}': Expect '{{' before function body.")]
     FunctionMissingLBraceFound(lexer::Token),
 
@@ -31,20 +38,62 @@ pub enum ParserErrorKind {
     #[error("Error at '{0}': {1}")]
     ExpectedIdentifierNotFound(lexer::Token, String),
 
-    #[error("Error at '{0}': Can't have more than 255 parameters.")]
-    ExcessParamtersFound(lexer::Token),
+    #[error("Error at '{0}': Can't have more than {1} parameters.")]
+    ExcessParamtersFound(lexer::Token, usize),
 
-    #[error("Error at '{0}': Can't have more than 255 arguments.")]
-    ExcessArgumentsFound(lexer::Token),
+    #[error("Error at '{0}': Can't have more than {1} arguments.")]
+    ExcessArgumentsFound(lexer::Token, usize),
 
     #[error("Error at '{0}': Already a variable with this name in this scope.")]
-    DuplicateParamter(String),
+    DuplicateParamter(lexer::Token),
 
     #[error("Error at '{0}': Expect expression.")]
-    ExpectExpressionFound(String),
+    ExpectExpressionFound(String, lexer::Span),
 
     #[error("Error at '{0}': Unable to parse ast float due to {1}.")]
     ParseFloatError(String, std::num::ParseFloatError),
+
+    #[error("Error at '{0}': Unable to parse ast integer due to {1}.")]
+    ParseIntError(String, std::num::ParseIntError),
+
+    #[error("{0}")]
+    WithSnippet(Box<ParserErrorKind>, String),
+}
+
+impl ParserErrorKind {
+    /// Position of the offending token, when the error carries one.
+    pub fn span(&self) -> Option<lexer::Span> {
+        use ParserErrorKind::*;
+        match self {
+            UnexpectedToken(token, _)
+            | FunctionMissingLBraceFound(token)
+            | ExpectedIdentifierNotFound(token, _)
+            | ExcessParamtersFound(token, _)
+            | ExcessArgumentsFound(token, _)
+            | DuplicateParamter(token) => Some(token.span),
+            ExpectExpressionFound(_, span) => Some(*span),
+            WithSnippet(e, _) => e.span(),
+            _ => None,
+        }
+    }
+
+    /// The offending source line with a caret under the error column, if the parser
+    /// was constructed with `Parser::new_with_source`.
+    pub fn snippet(&self) -> Option<&str> {
+        match self {
+            ParserErrorKind::WithSnippet(_, snippet) => Some(snippet),
+            _ => None,
+        }
+    }
+}
+
+pub fn format_snippet(line: &str, col: usize) -> String {
+    let padding: String = line
+        .chars()
+        .take(col.saturating_sub(1))
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    format!("{}\n{}^", line, padding)
 }
 
 type Result<T> = std::result::Result<T, ParserErrorKind>;
@@ -1,34 +1,111 @@
-use std::iter::Peekable;
-
 use crate::ast::*;
+use crate::format_snippet;
+use crate::lexer::Span;
 use crate::lexer::Token;
 use crate::lexer::TokenType;
 use crate::ParserErrorKind;
+use crate::PeekMore;
+use crate::PeekMoreIterator;
 use crate::Result;
 
 type ParseResult = Result<Expr>;
 type ParseStmtResult = Result<Stmt>;
 
+/// The most parameters or arguments a call may have unless configured otherwise.
+const DEFAULT_MAX_ARITY: usize = 255;
+
 pub struct Parser<I: Iterator<Item = Token>> {
-    i: Peekable<I>,
+    i: PeekMoreIterator<I>,
+    source: Option<Vec<String>>,
+    max_params: usize,
+    max_args: usize,
+    /// Type of the last token consumed.
+    previous: Option<TokenType>,
 }
 
 impl<I: Iterator<Item = Token>> Parser<I> {
     pub fn new(i: I) -> Self {
-        Self { i: i.peekable() }
+        Self {
+            i: i.peekmore(),
+            source: None,
+            max_params: DEFAULT_MAX_ARITY,
+            max_args: DEFAULT_MAX_ARITY,
+            previous: None,
+        }
+    }
+
+    /// Like `new`, but errors returned by `program` carry a snippet of the offending line.
+    pub fn new_with_source(i: I, source: &str) -> Self {
+        Self {
+            i: i.peekmore(),
+            source: Some(source.lines().map(String::from).collect()),
+            max_params: DEFAULT_MAX_ARITY,
+            max_args: DEFAULT_MAX_ARITY,
+            previous: None,
+        }
+    }
+
+    /// Rejects functions declaring more than `n` parameters, 255 by default.
+    pub fn with_max_params(mut self, n: usize) -> Self {
+        self.max_params = n;
+        self
+    }
+
+    /// Rejects calls passing more than `n` arguments, 255 by default.
+    pub fn with_max_args(mut self, n: usize) -> Self {
+        self.max_args = n;
+        self
+    }
+
+    fn with_snippet(&self, e: ParserErrorKind) -> ParserErrorKind {
+        let snippet = match (&self.source, e.span()) {
+            (Some(lines), Some(span)) => lines
+                .get(span.line - 1)
+                .map(|line| format_snippet(line, span.col)),
+            _ => None,
+        };
+        match snippet {
+            Some(snippet) => ParserErrorKind::WithSnippet(Box::new(e), snippet),
+            None => e,
+        }
+    }
+
+    #[inline(always)]
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.i.next();
+        self.previous = token.as_ref().map(|tok| tok.ty);
+        token
     }
 
     pub fn next_token(&mut self) -> Result<Token> {
-        match self.i.next() {
+        match self.advance() {
             Some(t) => Ok(t),
             None => Err(ParserErrorKind::MissingToken),
         }
     }
 
+    #[inline(always)]
+    fn peek(&mut self) -> Option<&Token> {
+        self.peek_nth(0)
+    }
+
+    /// Looks `n` tokens ahead without consuming anything, `peek_nth(0)` being the next token.
+    #[inline(always)]
+    fn peek_nth(&mut self, n: usize) -> Option<&Token> {
+        self.i.peek_nth(n)
+    }
+
+    fn peek_nth_expect(&mut self, n: usize, expected: TokenType) -> bool {
+        matches!(
+            self.peek_nth(n),
+            Some(actual) if actual.ty == expected
+        )
+    }
+
     fn expect(&mut self, expected: TokenType, err: &str) -> Result<()> {
-        match self.i.peek() {
+        match self.peek() {
             Some(actual) if actual.ty == expected => {
-                self.i.next();
+                self.advance();
                 Ok(())
             }
             Some(actual) => Err(ParserErrorKind::UnexpectedToken(actual.clone(), err.into())),
@@ -37,22 +114,58 @@ impl<I: Iterator<Item = Token>> Parser<I> {
     }
 
     fn peek_expect(&mut self, expected: TokenType) -> bool {
-        matches!(
-            self.i.peek(),
-            Some(actual) if actual.ty == expected
-        )
+        self.peek_nth_expect(0, expected)
+    }
+
+    /// Like `program`, but after an error skips to the next statement and keeps parsing,
+    /// so one pass reports every error it can find.
+    pub fn program_with_recovery(&mut self) -> (Vec<Stmt>, Vec<ParserErrorKind>) {
+        let (mut stmts, mut errors) = (Vec::new(), Vec::new());
+        while let Some(start) = self.peek().map(|tok| tok.span) {
+            match self.declaration() {
+                Ok(stmt) => stmts.push(stmt),
+                Err(e) => {
+                    errors.push(self.with_snippet(e));
+                    self.synchronize(start);
+                }
+            }
+        }
+        (stmts, errors)
+    }
+
+    /// Discards tokens up to the end of the statement that started at `start`, i.e. past
+    /// the next `;` or up to a keyword starting a new one.
+    fn synchronize(&mut self, start: Span) {
+        use TokenType::*;
+        // Make progress even when the statement failed on its very first token.
+        if self.peek().map(|tok| tok.span) == Some(start) {
+            self.advance();
+        }
+        while self.previous != Some(SemiColon) {
+            match self.peek().map(|tok| tok.ty) {
+                None | Some(Class | Fun | Var | For | If | While | Print | Return | Import) => {
+                    return
+                }
+                Some(_) => {
+                    self.advance();
+                }
+            }
+        }
     }
 
     pub fn program(&mut self) -> Result<Vec<Stmt>> {
         let mut stmts = Vec::new();
-        while let Some(_tok) = self.i.peek() {
-            stmts.push(self.declaration()?);
+        while let Some(_tok) = self.peek() {
+            match self.declaration() {
+                Ok(stmt) => stmts.push(stmt),
+                Err(e) => return Err(self.with_snippet(e)),
+            }
         }
         Ok(stmts)
     }
 
     fn declaration(&mut self) -> ParseStmtResult {
-        match self.i.peek() {
+        match self.peek() {
             Some(t) if t.ty == TokenType::Class => self.class_decl(),
             Some(t) if t.ty == TokenType::Var => self.var_decl(),
             Some(t) if t.ty == TokenType::Fun => self.fun_decl(),
@@ -80,10 +193,10 @@ impl<I: Iterator<Item = Token>> Parser<I> {
             let name = self.identifier("Expect class method name.")?;
 
             self.expect(TokenType::LeftParen, "expected ( after function name")?;
-            let params = if !self.peek_expect(TokenType::RightParen) {
+            let (params, annotations) = if !self.peek_expect(TokenType::RightParen) {
                 self.parameters()?
             } else {
-                Vec::new()
+                Default::default()
             };
             self.expect(TokenType::RightParen, "expected ) after function params")?;
             let body = self.block()?;
@@ -97,6 +210,7 @@ impl<I: Iterator<Item = Token>> Parser<I> {
             methods.push(FunctionDecl {
                 name,
                 params,
+                annotations,
                 body: stmts,
             })
         }
@@ -118,14 +232,14 @@ impl<I: Iterator<Item = Token>> Parser<I> {
         let name = self.identifier("Expect function name.")?;
 
         self.expect(TokenType::LeftParen, "Expect '(' after function name")?;
-        let params = if !self.peek_expect(TokenType::RightParen) {
+        let (params, annotations) = if !self.peek_expect(TokenType::RightParen) {
             self.parameters()?
         } else {
-            Vec::new()
+            Default::default()
         };
 
         self.expect(TokenType::RightParen, "Expect ')' after parameters.")?;
-        let body = match self.i.peek() {
+        let body = match self.peek() {
             Some(tok) if tok.ty == TokenType::LeftBrace => self.block()?,
             Some(tok) => return Err(ParserErrorKind::FunctionMissingLBraceFound(tok.clone())),
             _ => return Err(ParserErrorKind::FunctionMissingLBrace),
@@ -140,40 +254,56 @@ impl<I: Iterator<Item = Token>> Parser<I> {
         Ok(Stmt::FunctionDecl(FunctionDecl {
             name,
             params,
+            annotations,
             body: stmts,
         }))
     }
 
     fn identifier(&mut self, err: &str) -> Result<Identifier> {
-        match self.next_token()? {
-            token if token.ty == TokenType::Ident => Ok(Identifier { token, rid: 0 }),
-            x => Err(ParserErrorKind::ExpectedIdentifierNotFound(x, err.into())),
+        match self.advance() {
+            Some(token) if token.ty == TokenType::Ident => Ok(token.into()),
+            Some(x) => Err(ParserErrorKind::ExpectedIdentifierNotFound(x, err.into())),
+            None => Err(ParserErrorKind::UnexpectedEof(err.into())),
         }
     }
 
-    fn parameters(&mut self) -> Result<Vec<Identifier>> {
-        let mut params = vec![self.identifier("Expect parameter name.")?];
-        while let Some(tok) = self.i.peek() {
-            match tok.ty {
-                TokenType::Comma => {
-                    self.next_token()?;
-                    if params.len() >= 255 {
-                        return Err(ParserErrorKind::ExcessParamtersFound(
-                            self.i.peek().unwrap().clone(),
-                        ));
-                    } else {
-                        let id = self.identifier("Expect parameter name.")?;
-                        if params.iter().any(|i| i.token.lexeme == id.token.lexeme) {
-                            return Err(ParserErrorKind::DuplicateParamter(id.token.lexeme));
-                        } else {
-                            params.push(id);
-                        }
-                    }
+    /// A parameter name with an optional `: type` annotation.
+    fn parameter(&mut self) -> Result<(Identifier, Option<Box<str>>)> {
+        let param = self.identifier("Expect parameter name.")?;
+        if !self.peek_expect(TokenType::Colon) {
+            return Ok((param, None));
+        }
+        self.next_token()?;
+        let ty = self.identifier("Expect parameter type.")?;
+        Ok((param, Some(ty.token.lexeme.into())))
+    }
+
+    fn parameters(&mut self) -> Result<(Vec<Identifier>, Annotations)> {
+        let mut params: Vec<Identifier> = vec![];
+        let mut annotations = vec![];
+        let max = self.max_params;
+        loop {
+            match self.peek() {
+                Some(tok) if params.len() >= max => {
+                    return Err(ParserErrorKind::ExcessParamtersFound(tok.clone(), max))
                 }
-                _ => break,
+                _ => {}
+            }
+            let (id, annotation) = self.parameter()?;
+            if params.iter().any(|i| i.token.lexeme == id.token.lexeme) {
+                return Err(ParserErrorKind::DuplicateParamter(id.token));
+            }
+            params.push(id);
+            annotations.push(annotation);
+            if !self.peek_expect(TokenType::Comma) {
+                break;
             }
+            self.next_token()?;
+        }
+        if annotations.iter().all(Option::is_none) {
+            annotations.clear();
         }
-        Ok(params)
+        Ok((params, annotations))
     }
 
     fn var_decl(&mut self) -> ParseStmtResult {
@@ -199,7 +329,7 @@ impl<I: Iterator<Item = Token>> Parser<I> {
     }
 
     fn statement(&mut self) -> ParseStmtResult {
-        match self.i.peek() {
+        match self.peek() {
             Some(tok) => match tok.ty {
                 TokenType::Print => self.print_stmt(),
                 TokenType::LeftBrace => self.block(),
@@ -208,6 +338,8 @@ impl<I: Iterator<Item = Token>> Parser<I> {
                 TokenType::While => self.while_stmt(),
                 TokenType::For => self.for_stmt(),
                 TokenType::Break => self.break_stmt(),
+                TokenType::Continue => self.continue_stmt(),
+                TokenType::Import => self.import_stmt(),
                 _ => self.expr_stmt(),
             },
             None => unreachable!(),
@@ -221,11 +353,17 @@ impl<I: Iterator<Item = Token>> Parser<I> {
     }
 
     fn for_stmt(&mut self) -> ParseStmtResult {
+        if self.peek_nth_expect(2, TokenType::Var)
+            && self.peek_nth_expect(3, TokenType::Ident)
+            && matches!(self.peek_nth(4), Some(tok) if tok.ty == TokenType::Ident && tok.lexeme == "in")
+        {
+            return self.for_each_stmt();
+        }
         self.expect(TokenType::For, "for loop must start with for keyword")?;
         self.expect(TokenType::LeftParen, "expected ( at the start of for loop")?;
         let mut block = Vec::new();
 
-        let initializer = match self.i.peek() {
+        let initializer = match self.peek() {
             Some(tok) if tok.ty == TokenType::SemiColon => {
                 self.expect(
                     TokenType::SemiColon,
@@ -257,37 +395,95 @@ impl<I: Iterator<Item = Token>> Parser<I> {
             None
         };
         self.expect(TokenType::RightParen, "expected ) after for loop")?;
-        let body = match self.i.peek() {
+        let body = match self.peek() {
             Some(tok) if tok.ty == TokenType::Class || tok.ty == TokenType::Fun => {
-                return Err(ParserErrorKind::ExpectExpressionFound(match tok.ty {
-                    TokenType::Class => "class".into(),
-                    TokenType::Fun => "fun".into(),
-                    _ => unreachable!(),
-                }))
+                return Err(ParserErrorKind::ExpectExpressionFound(
+                    tok.ty.to_string(),
+                    tok.span,
+                ))
             }
             _ => self.statement()?,
         };
-        let body = if let Some(update) = update {
-            vec![body, Stmt::Expr(update)]
-        } else {
-            vec![body]
-        };
 
-        block.push(Stmt::Loop(Loop {
+        // The update is kept out of the body so that `continue` does not skip it.
+        let for_loop = Stmt::Loop(Loop {
             cond,
-            body: Box::new(Stmt::Block(body)),
-        }));
+            body: Box::new(body),
+            update,
+        });
+        if block.is_empty() {
+            return Ok(for_loop);
+        }
+        block.push(for_loop);
 
         Ok(Stmt::Block(block))
     }
 
-    fn break_stmt(&mut self) -> ParseStmtResult {
+    fn for_each_stmt(&mut self) -> ParseStmtResult {
+        self.expect(TokenType::For, "for loop must start with for keyword")?;
+        self.expect(TokenType::LeftParen, "expected ( at the start of for loop")?;
+        self.expect(TokenType::Var, "expected var in for-each loop")?;
+        let name = self.identifier("Expect variable name.")?;
         self.next_token()?;
+        let iterable = self.expression()?;
+        self.expect(TokenType::RightParen, "expected ) after for loop")?;
+        let body = match self.peek() {
+            Some(tok) if tok.ty == TokenType::Class || tok.ty == TokenType::Fun => {
+                return Err(ParserErrorKind::ExpectExpressionFound(
+                    tok.ty.to_string(),
+                    tok.span,
+                ))
+            }
+            _ => self.statement()?,
+        };
+        Ok(Stmt::ForEach(ForEach {
+            name,
+            iterable,
+            body: Box::new(body),
+        }))
+    }
+
+    fn break_stmt(&mut self) -> ParseStmtResult {
+        let span = self.next_token()?.span;
         self.expect(
             TokenType::SemiColon,
             "Error: Expect ';' at the end of break statement.",
         )?;
-        Ok(Stmt::Break)
+        Ok(Stmt::Break(span))
+    }
+
+    fn continue_stmt(&mut self) -> ParseStmtResult {
+        let span = self.next_token()?.span;
+        self.expect(
+            TokenType::SemiColon,
+            "Error: Expect ';' at the end of continue statement.",
+        )?;
+        Ok(Stmt::Continue(span))
+    }
+
+    fn import_stmt(&mut self) -> ParseStmtResult {
+        let span = self.next_token()?.span;
+        let path = match self.next_token()? {
+            token if token.ty == TokenType::Str => token.lexeme,
+            token => {
+                return Err(ParserErrorKind::UnexpectedToken(
+                    token,
+                    "Expect file path after 'import'.".into(),
+                ))
+            }
+        };
+        let alias = match self.peek() {
+            Some(tok) if tok.ty == TokenType::Ident && tok.lexeme == "as" => {
+                self.next_token()?;
+                Some(self.identifier("Expect module name after 'as'.")?)
+            }
+            _ => None,
+        };
+        self.expect(
+            TokenType::SemiColon,
+            "Error: Expect ';' at the end of import statement.",
+        )?;
+        Ok(Stmt::Import(Import { path, alias, span }))
     }
 
     fn if_stmt(&mut self) -> ParseStmtResult {
@@ -301,25 +497,23 @@ impl<I: Iterator<Item = Token>> Parser<I> {
             TokenType::RightParen,
             "condition in if statement must end with )",
         )?;
-        let if_branch = match self.i.peek() {
+        let if_branch = match self.peek() {
             Some(tok) if tok.ty == TokenType::Class || tok.ty == TokenType::Fun => {
-                return Err(ParserErrorKind::ExpectExpressionFound(match tok.ty {
-                    TokenType::Class => "class".into(),
-                    TokenType::Fun => "fun".into(),
-                    _ => unreachable!(),
-                }))
+                return Err(ParserErrorKind::ExpectExpressionFound(
+                    tok.ty.to_string(),
+                    tok.span,
+                ))
             }
             _ => self.statement()?,
         };
         let else_branch = if self.peek_expect(TokenType::Else) {
             self.next_token()?;
-            Some(Box::new(match self.i.peek() {
+            Some(Box::new(match self.peek() {
                 Some(tok) if tok.ty == TokenType::Class || tok.ty == TokenType::Fun => {
-                    return Err(ParserErrorKind::ExpectExpressionFound(match tok.ty {
-                        TokenType::Class => "class".into(),
-                        TokenType::Fun => "fun".into(),
-                        _ => unreachable!(),
-                    }))
+                    return Err(ParserErrorKind::ExpectExpressionFound(
+                        tok.ty.to_string(),
+                        tok.span,
+                    ))
                 }
                 _ => self.statement()?,
             }))
@@ -347,6 +541,7 @@ impl<I: Iterator<Item = Token>> Parser<I> {
     }
 
     fn return_stmt(&mut self) -> ParseStmtResult {
+        let span = self.peek().map(|tok| tok.span).unwrap_or_default();
         self.expect(
             TokenType::Return,
             "return statement must begin with return keyword",
@@ -360,19 +555,18 @@ impl<I: Iterator<Item = Token>> Parser<I> {
             TokenType::SemiColon,
             "expected ; at the end of return statement",
         )?;
-        Ok(Stmt::Return(value))
+        Ok(Stmt::Return(value, span))
     }
 
     fn while_stmt(&mut self) -> ParseStmtResult {
         self.expect(TokenType::While, "while loop must begin with while keyword")?;
         let cond = self.expression()?;
-        let body = match self.i.peek() {
+        let body = match self.peek() {
             Some(tok) if tok.ty == TokenType::Class || tok.ty == TokenType::Fun => {
-                return Err(ParserErrorKind::ExpectExpressionFound(match tok.ty {
-                    TokenType::Class => "class".into(),
-                    TokenType::Fun => "fun".into(),
-                    _ => unreachable!(),
-                }))
+                return Err(ParserErrorKind::ExpectExpressionFound(
+                    tok.ty.to_string(),
+                    tok.span,
+                ))
             }
             _ => {
                 let body = self.statement()?;
@@ -386,6 +580,7 @@ impl<I: Iterator<Item = Token>> Parser<I> {
         Ok(Stmt::Loop(Loop {
             cond,
             body: Box::new(Stmt::Block(body)),
+            update: None,
         }))
     }
 
@@ -413,12 +608,12 @@ impl<I: Iterator<Item = Token>> Parser<I> {
         let ast = self.logic_or()?;
 
         Ok(if self.peek_expect(TokenType::Eq) {
-            self.expect(TokenType::Eq, "expected = in variable assignment")?;
+            let span = self.next_token()?.span;
             let inner = self.assignment()?;
             if let Expr::Get(object, property) = ast {
                 Expr::Set(object, property, Box::new(inner))
             } else {
-                Expr::Assign(Box::new(ast), Box::new(inner))
+                Expr::Assign(Box::new(ast), Box::new(inner), span)
             }
         } else {
             ast
@@ -427,12 +622,12 @@ impl<I: Iterator<Item = Token>> Parser<I> {
 
     fn logic_or(&mut self) -> ParseResult {
         let mut ast = self.logic_and()?;
-        while let Some(tok) = self.i.peek() {
+        while let Some(tok) = self.peek() {
             match tok.ty {
                 TokenType::Or => {
-                    self.next_token()?;
+                    let span = self.next_token()?.span;
                     let inner = self.logic_and()?;
-                    ast = Expr::Logical(BinaryOp::Or, Box::new(ast), Box::new(inner));
+                    ast = Expr::Logical(BinaryOp::Or, Box::new(ast), Box::new(inner), span);
                 }
                 _ => break,
             }
@@ -442,12 +637,12 @@ impl<I: Iterator<Item = Token>> Parser<I> {
 
     fn logic_and(&mut self) -> ParseResult {
         let mut ast = self.equality()?;
-        while let Some(tok) = self.i.peek() {
+        while let Some(tok) = self.peek() {
             match tok.ty {
                 TokenType::And => {
-                    self.next_token()?;
+                    let span = self.next_token()?.span;
                     let inner = self.equality()?;
-                    ast = Expr::Logical(BinaryOp::And, Box::new(ast), Box::new(inner));
+                    ast = Expr::Logical(BinaryOp::And, Box::new(ast), Box::new(inner), span);
                 }
                 _ => break,
             }
@@ -457,13 +652,13 @@ impl<I: Iterator<Item = Token>> Parser<I> {
 
     fn equality(&mut self) -> ParseResult {
         let mut ast = self.comparison()?;
-        while let Some(tok) = self.i.peek() {
+        while let Some(tok) = self.peek() {
             match tok.ty {
                 TokenType::Ne | TokenType::Deq => {
                     let bop: BinaryOp = tok.ty.into();
-                    self.next_token()?;
+                    let span = self.next_token()?.span;
                     let inner = self.comparison()?;
-                    ast = Expr::Binary(bop, Box::new(ast), Box::new(inner))
+                    ast = Expr::Binary(bop, Box::new(ast), Box::new(inner), span)
                 }
                 _ => break,
             }
@@ -473,13 +668,13 @@ impl<I: Iterator<Item = Token>> Parser<I> {
 
     fn comparison(&mut self) -> ParseResult {
         let mut ast = self.term()?;
-        while let Some(tok) = self.i.peek() {
+        while let Some(tok) = self.peek() {
             match tok.ty {
                 TokenType::Lt | TokenType::Gt | TokenType::Le | TokenType::Ge => {
                     let bop: BinaryOp = tok.ty.into();
-                    self.next_token()?;
+                    let span = self.next_token()?.span;
                     let inner = self.term()?;
-                    ast = Expr::Binary(bop, Box::new(ast), Box::new(inner))
+                    ast = Expr::Binary(bop, Box::new(ast), Box::new(inner), span)
                 }
                 _ => break,
             }
@@ -489,13 +684,13 @@ impl<I: Iterator<Item = Token>> Parser<I> {
 
     fn term(&mut self) -> ParseResult {
         let mut ast = self.factor()?;
-        while let Some(tok) = self.i.peek() {
+        while let Some(tok) = self.peek() {
             match tok.ty {
                 TokenType::Plus | TokenType::Minus => {
                     let bop: BinaryOp = tok.ty.into();
-                    self.next_token()?;
+                    let span = self.next_token()?.span;
                     let inner = self.factor()?;
-                    ast = Expr::Binary(bop, Box::new(ast), Box::new(inner))
+                    ast = Expr::Binary(bop, Box::new(ast), Box::new(inner), span)
                 }
                 _ => break,
             }
@@ -505,13 +700,13 @@ impl<I: Iterator<Item = Token>> Parser<I> {
 
     fn factor(&mut self) -> ParseResult {
         let mut ast = self.unary()?;
-        while let Some(tok) = self.i.peek() {
+        while let Some(tok) = self.peek() {
             match tok.ty {
                 TokenType::Star | TokenType::ForwardSlash => {
                     let bop: BinaryOp = tok.ty.into();
-                    self.next_token()?;
+                    let span = self.next_token()?.span;
                     let inner = self.unary()?;
-                    ast = Expr::Binary(bop, Box::new(ast), Box::new(inner))
+                    ast = Expr::Binary(bop, Box::new(ast), Box::new(inner), span)
                 }
                 _ => break,
             }
@@ -520,16 +715,16 @@ impl<I: Iterator<Item = Token>> Parser<I> {
     }
 
     fn unary(&mut self) -> ParseResult {
-        match self.i.peek() {
+        match self.peek() {
             Some(tok) if (tok.ty == TokenType::Not || tok.ty == TokenType::Minus) => {
                 let uop = match tok.ty {
                     TokenType::Not => UnaryOp::Not,
                     TokenType::Minus => UnaryOp::Minus,
                     _ => unreachable!(),
                 };
-                self.next_token()?;
+                let span = self.next_token()?.span;
                 let ast = self.unary()?;
-                Ok(Expr::Unary(uop, Box::new(ast)))
+                Ok(Expr::Unary(uop, Box::new(ast), span))
             }
             _ => self.call(),
         }
@@ -537,10 +732,10 @@ impl<I: Iterator<Item = Token>> Parser<I> {
 
     fn call(&mut self) -> ParseResult {
         let mut callee = self.primary()?;
-        while let Some(tok) = self.i.peek() {
+        while let Some(tok) = self.peek() {
             match tok.ty {
                 TokenType::LeftParen => {
-                    self.next_token()?;
+                    let span = self.next_token()?.span;
                     let args = if self.peek_expect(TokenType::RightParen) {
                         Vec::new()
                     } else {
@@ -550,13 +745,18 @@ impl<I: Iterator<Item = Token>> Parser<I> {
                         TokenType::RightParen,
                         "expected ) after params in call statement",
                     )?;
-                    callee = Expr::Call(Box::new(callee), args);
+                    callee = Expr::Call(Box::new(callee), args, span);
                 }
                 TokenType::Dot => {
                     self.next_token()?;
                     let ident = self.identifier("Expect property name after '.'.")?;
                     callee = Expr::Get(Box::new(callee), ident);
                 }
+                TokenType::QuestionDot => {
+                    self.next_token()?;
+                    let ident = self.identifier("Expect property name after '?.'.")?;
+                    callee = Expr::GetSafe(Box::new(callee), ident);
+                }
                 _ => break,
             }
         }
@@ -564,34 +764,73 @@ impl<I: Iterator<Item = Token>> Parser<I> {
     }
 
     fn arguments(&mut self) -> Result<Arguments> {
-        let mut args = vec![self.expression()?.into()];
-        while let Some(tok) = self.i.peek() {
-            match tok.ty {
-                TokenType::Comma => {
-                    self.next_token()?;
-                    if args.len() >= 255 {
-                        return Err(ParserErrorKind::ExcessArgumentsFound(
-                            self.i.peek().unwrap().clone(),
-                        ));
-                    } else {
-                        args.push(self.expression()?.into());
-                    }
+        let mut args = vec![];
+        let max = self.max_args;
+        loop {
+            match self.peek() {
+                Some(tok) if args.len() >= max => {
+                    return Err(ParserErrorKind::ExcessArgumentsFound(tok.clone(), max))
                 }
-                _ => break,
+                _ => {}
+            }
+            args.push(self.expression()?.into());
+            if !self.peek_expect(TokenType::Comma) {
+                break;
             }
+            self.next_token()?;
         }
         Ok(args)
     }
 
+    /// Desugars `"a${x}b"` into `"a" + "${x}" + "b"`, where `"${x}"` is an
+    /// `Expr::Interpolation` converting `x` to a string.
+    fn interpolation(&mut self, head: Token) -> ParseResult {
+        let concat =
+            |lhs, rhs, span| Expr::Binary(BinaryOp::Add, Box::new(lhs), Box::new(rhs), span);
+        let mut span = head.span;
+        let mut expr = (!head.lexeme.is_empty()).then_some(Expr::String(head.lexeme));
+        loop {
+            let inner = Expr::Interpolation(Box::new(self.expression()?));
+            let mut joined = match expr {
+                Some(lhs) => concat(lhs, inner, span),
+                None => inner,
+            };
+
+            let fragment = self.next_token()?;
+            if !matches!(fragment.ty, TokenType::Interpolation | TokenType::Str) {
+                return Err(ParserErrorKind::UnexpectedToken(
+                    fragment,
+                    "Expect '}' after interpolated expression.".into(),
+                ));
+            }
+            let done = fragment.ty == TokenType::Str;
+            span = fragment.span;
+            if !fragment.lexeme.is_empty() {
+                joined = concat(joined, Expr::String(fragment.lexeme), span);
+            }
+            if done {
+                return Ok(joined);
+            }
+            expr = Some(joined);
+        }
+    }
+
     fn primary(&mut self) -> ParseResult {
         let next = self.next_token()?;
         Ok(match next.ty {
             TokenType::Str => Expr::String(next.lexeme),
-            TokenType::Numeric => match next.lexeme.parse::<i64>() {
-                Ok(i) => Expr::Int(i),
-                Err(_) => match next.lexeme.parse::<f64>() {
-                    Ok(f) => Expr::Float(f),
-                    Err(e) => return Err(ParserErrorKind::ParseFloatError(next.lexeme, e)),
+            TokenType::Interpolation => self.interpolation(next)?,
+            TokenType::Numeric => match radix_prefix(&next.lexeme) {
+                Some(radix) => match i64::from_str_radix(&next.lexeme[2..], radix) {
+                    Ok(i) => Expr::Int(i),
+                    Err(e) => return Err(ParserErrorKind::ParseIntError(next.lexeme, e)),
+                },
+                None => match next.lexeme.parse::<i64>() {
+                    Ok(i) => Expr::Int(i),
+                    Err(_) => match next.lexeme.parse::<f64>() {
+                        Ok(f) => Expr::Float(f),
+                        Err(e) => return Err(ParserErrorKind::ParseFloatError(next.lexeme, e)),
+                    },
                 },
             },
             TokenType::Nil => Expr::Nil,
@@ -608,10 +847,10 @@ impl<I: Iterator<Item = Token>> Parser<I> {
                     TokenType::LeftParen,
                     "expected ( before params in anonymous function",
                 )?;
-                let params = if !self.peek_expect(TokenType::RightParen) {
+                let (params, annotations) = if !self.peek_expect(TokenType::RightParen) {
                     self.parameters()?
                 } else {
-                    Vec::new()
+                    Default::default()
                 };
                 self.expect(
                     TokenType::RightParen,
@@ -625,32 +864,35 @@ impl<I: Iterator<Item = Token>> Parser<I> {
                     vec![]
                 };
 
-                Expr::Lambda(params, stmts)
+                Expr::Lambda(params, annotations, stmts)
             }
-            TokenType::Ident => Expr::Ident(Identifier {
-                token: next,
-                rid: 0,
-            }),
-            TokenType::This => Expr::This(Identifier {
-                token: next,
-                rid: 0,
-            }),
+            TokenType::Ident => Expr::Ident(next.into()),
+            TokenType::This => Expr::This(next.into()),
             TokenType::Super => {
                 self.expect(TokenType::Dot, "Expect '.' after 'super'.")?;
                 let method = self.identifier("Expect superclass method name.")?;
-                Expr::Super(
-                    Identifier {
-                        token: next,
-                        rid: 0,
-                    },
-                    method,
-                )
+                Expr::Super(next.into(), method)
+            }
+            _elt => {
+                return Err(ParserErrorKind::ExpectExpressionFound(
+                    next.lexeme,
+                    next.span,
+                ))
             }
-            _elt => return Err(ParserErrorKind::ExpectExpressionFound(next.lexeme)),
         })
     }
 }
 
+#[inline(always)]
+fn radix_prefix(lexeme: &str) -> Option<u32> {
+    match lexeme.get(..2) {
+        Some("0x" | "0X") => Some(16),
+        Some("0b" | "0B") => Some(2),
+        Some("0o" | "0O") => Some(8),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -658,16 +900,22 @@ mod tests {
     use crate::lexer::Span;
     use crate::lexer::Token;
 
+    fn tokens(input: &str) -> Vec<Token> {
+        let lexer = Lexer::new(input.chars()).unwrap();
+        let tokens: std::result::Result<Vec<Token>, _> = lexer.into_iter().collect();
+        tokens.expect("lexing error")
+    }
+
+    fn parse(input: &str) -> Result<Vec<Stmt>> {
+        Parser::new(tokens(input).into_iter()).program()
+    }
+
     #[allow(unused_macros)]
     macro_rules! test_parse {
         ($name: ident,$input: literal,$tt: expr) => {
             #[test]
             fn $name() {
-                let input = $input;
-                let lexer = Lexer::new(input.chars()).unwrap();
-                let tokens: std::result::Result<Vec<Token>, _> = lexer.into_iter().collect();
-                let tokens = tokens.expect("lexing error");
-                let ast = Parser::new(tokens.into_iter())
+                let ast = Parser::new(tokens($input).into_iter())
                     .expression()
                     .expect("parsing error");
 
@@ -682,6 +930,10 @@ mod tests {
         "((\"this is a string\"))",
         Expr::String("this is a string".into())
     );
+    test_parse!(hex_ff, "0xFF", Expr::Int(255));
+    test_parse!(hex_10, "0x10", Expr::Int(16));
+    test_parse!(bin_1010, "0b1010", Expr::Int(10));
+    test_parse!(oct_17, "0o17", Expr::Int(15));
     test_parse!(true_expr, "true", Expr::Boolean(true));
     test_parse!(false_expr, "false", Expr::Boolean(false));
     test_parse!(nil, "nil", Expr::Nil);
@@ -693,9 +945,11 @@ mod tests {
             Box::new(Expr::Binary(
                 BinaryOp::Mul,
                 Box::new(Expr::Float(0.1),),
-                Box::new(Expr::Float(0.2),)
+                Box::new(Expr::Float(0.2),),
+                Span::new(1, 5)
             )),
-            Box::new(Expr::Float(0.3))
+            Box::new(Expr::Float(0.3)),
+            Span::new(1, 10)
         )
     );
     test_parse!(
@@ -707,8 +961,10 @@ mod tests {
             Box::new(Expr::Binary(
                 BinaryOp::Mul,
                 Box::new(Expr::Float(0.2),),
-                Box::new(Expr::Float(0.3),)
+                Box::new(Expr::Float(0.3),),
+                Span::new(1, 10)
             )),
+            Span::new(1, 5)
         )
     );
 
@@ -717,12 +973,18 @@ mod tests {
         "!0.1 + 0.2* 0.3",
         Expr::Binary(
             BinaryOp::Add,
-            Box::new(Expr::Unary(UnaryOp::Not, Box::new(Expr::Float(0.1)))),
+            Box::new(Expr::Unary(
+                UnaryOp::Not,
+                Box::new(Expr::Float(0.1)),
+                Span::new(1, 1)
+            )),
             Box::new(Expr::Binary(
                 BinaryOp::Mul,
                 Box::new(Expr::Float(0.2),),
-                Box::new(Expr::Float(0.3),)
+                Box::new(Expr::Float(0.3),),
+                Span::new(1, 11)
             )),
+            Span::new(1, 6)
         )
     );
 
@@ -737,9 +999,47 @@ mod tests {
                 Box::new(Expr::Binary(
                     BinaryOp::Mul,
                     Box::new(Expr::Float(0.2),),
-                    Box::new(Expr::Float(0.3),)
+                    Box::new(Expr::Float(0.3),),
+                    Span::new(1, 12)
                 )),
-            ))
+                Span::new(1, 7)
+            )),
+            Span::new(1, 1)
+        )
+    );
+
+    test_parse!(
+        method_call_on_number,
+        "123.method()",
+        Expr::Call(
+            Box::new(Expr::Get(
+                Box::new(Expr::Int(123)),
+                Token::new_with_lexeme(TokenType::Ident, "method", Span::new(1, 5)).into()
+            )),
+            vec![],
+            Span::new(1, 11)
+        )
+    );
+
+    test_parse!(
+        call_and_logical,
+        "f(a) or b",
+        Expr::Logical(
+            BinaryOp::Or,
+            Box::new(Expr::Call(
+                Box::new(Expr::Ident(
+                    Token::new_with_lexeme(TokenType::Ident, "f", Span::new(1, 1)).into()
+                )),
+                vec![Expr::Ident(
+                    Token::new_with_lexeme(TokenType::Ident, "a", Span::new(1, 3)).into()
+                )
+                .into()],
+                Span::new(1, 2)
+            )),
+            Box::new(Expr::Ident(
+                Token::new_with_lexeme(TokenType::Ident, "b", Span::new(1, 9)).into()
+            )),
+            Span::new(1, 6)
         )
     );
 
@@ -748,9 +1048,282 @@ mod tests {
         "fun (a){print a;}",
         Expr::Lambda(
             vec![Token::new_with_lexeme(TokenType::Ident, "a", Span::new(1, 6)).into()],
+            vec![],
             vec![Stmt::Print(Expr::Ident(
                 Token::new_with_lexeme(TokenType::Ident, "a", Span::new(1, 15)).into()
             ))]
         )
     );
+
+    #[allow(unused_macros)]
+    macro_rules! test_format {
+        ($name: ident,$input: literal,$formatted: literal) => {
+            #[test]
+            fn $name() {
+                // Formatting moves tokens around, so compare the programs without spans.
+                let parse = |input: &str| {
+                    let tokens = tokens(input).into_iter().map(|token| Token {
+                        span: Span::default(),
+                        ..token
+                    });
+                    Parser::new(tokens).program().expect("parsing error")
+                };
+
+                let ast = parse($input);
+                let formatted = format_source(&ast);
+                assert_eq!(formatted, $formatted);
+                assert_eq!(parse(&formatted), ast);
+            }
+        };
+    }
+
+    test_format!(
+        format_expressions,
+        "var a=1+2*3;print (a-1)/(2*-a);a=b=!(a==1 or b and c);",
+        "var a = 1 + 2 * 3;\nprint (a - 1) / (2 * -a);\na = b = !(a == 1 or b and c);\n"
+    );
+
+    test_format!(
+        format_control_flow,
+        "fun f(n){if(n<2)return n;else{return f(n-1)+f(n-2);}} while(true){break;}",
+        r#"fun f(n) {
+  if (n < 2)
+    return n;
+  else {
+    return f(n - 1) + f(n - 2);
+  }
+}
+while (true) {
+  break;
+}
+"#
+    );
+
+    test_format!(
+        format_class,
+        "class A<B{init(x){this.x=x;return;} get(){return super.get()+1.0;}} print A(0.5).get;",
+        r#"class A < B {
+  init(x) {
+    this.x = x;
+    return;
+  }
+  get() {
+    return super.get() + 1.0;
+  }
+}
+print A(0.5).get;
+"#
+    );
+
+    test_format!(
+        format_interpolation,
+        r#"print "a${x+1}b\${c}" + r"${";"#,
+        r#"print "a" + "${x + 1}" + "b\${c}" + "\${";
+"#
+    );
+
+    test_format!(
+        format_annotations,
+        "fun add(a:int,b: number){return a+b;}",
+        r#"fun add(a: int, b: number) {
+  return a + b;
+}
+"#
+    );
+
+    test_format!(
+        format_optional_chaining,
+        "print a?.b.c?.d();",
+        "print a?.b.c?.d();\n"
+    );
+
+    test_format!(
+        format_imports,
+        "import \"a.lox\";import \"lib/b.lox\"  as b;",
+        "import \"a.lox\";\nimport \"lib/b.lox\" as b;\n"
+    );
+
+    test_format!(
+        format_for_and_lambda,
+        "for(var i=0;i<3;i=i+1){print fun(a){return a;}(i);}",
+        r#"{
+  var i = 0;
+  for (; i < 3; i = i + 1) {
+    print fun (a) {
+      return a;
+    }(i);
+  }
+}
+"#
+    );
+
+    test_format!(
+        format_raw_strings,
+        r###"print r#"say "hi""#; print r"${x}"; print r##"a"#b"##;"###,
+        r###"print r#"say "hi""#;
+print "\${x}";
+print r##"a"#b"##;
+"###
+    );
+
+    test_format!(
+        format_continue,
+        "while(true){continue;} for(;;){continue;}",
+        "while (true) {\n  continue;\n}\nwhile (true) {\n  continue;\n}\n"
+    );
+
+    #[test]
+    fn error_snippet() {
+        let input = "var a = 1;\n\tprint a b;";
+        let e = Parser::new_with_source(tokens(input).into_iter(), input)
+            .program()
+            .expect_err("expected a parsing error");
+        assert_eq!(
+            e.to_string(),
+            "Error at 'b': expected ; at the end of print statement"
+        );
+        assert_eq!(e.snippet(), Some("\tprint a b;\n\t        ^"));
+
+        let e = parse(input).expect_err("expected a parsing error");
+        assert_eq!(e.snippet(), None);
+    }
+
+    #[test]
+    fn property_name_at_eof() {
+        let e = parse("123.").expect_err("expected a parsing error");
+        assert_eq!(
+            e.to_string(),
+            "Error at end: Expect property name after '.'."
+        );
+    }
+
+    #[test]
+    fn error_near_fun_keyword() {
+        let e = parse("var fun = 1;").expect_err("expected a parsing error");
+        assert_eq!(e.to_string(), "Error at 'fun': Expect variable name.");
+    }
+
+    #[test]
+    fn duplicate_parameters_compare_names() {
+        for (input, name) in [
+            ("fun f(a, b, a) {}", "a"),
+            ("fun f(a,\n      b,\n      a) {}", "a"),
+            ("var f = fun (x: int, x) {};", "x"),
+        ] {
+            let e = parse(input).expect_err("expected a parsing error");
+            assert_eq!(
+                e.to_string(),
+                format!(
+                    "Error at '{}': Already a variable with this name in this scope.",
+                    name
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn configurable_arity_limits() {
+        let parse = |input: &str, max: usize| {
+            Parser::new(tokens(input).into_iter())
+                .with_max_params(max)
+                .with_max_args(max)
+                .program()
+        };
+        assert!(parse("fun f(a, b) {} f(1, 2);", 2).is_ok());
+        assert_eq!(
+            parse("fun f(a, b, c) {}", 2).unwrap_err().to_string(),
+            "Error at 'c': Can't have more than 2 parameters."
+        );
+        assert_eq!(
+            parse("f(1, 2, 3);", 2).unwrap_err().to_string(),
+            "Error at '3': Can't have more than 2 arguments."
+        );
+
+        assert!(parse("fun f() {} f();", 0).is_ok());
+        assert_eq!(
+            parse("fun f(a) {}", 0).unwrap_err().to_string(),
+            "Error at 'a': Can't have more than 0 parameters."
+        );
+        assert_eq!(
+            parse("f(1);", 0).unwrap_err().to_string(),
+            "Error at '1': Can't have more than 0 arguments."
+        );
+    }
+
+    #[test]
+    fn recover_after_errors() {
+        let (stmts, errors) =
+            Parser::new(tokens("var = 1;\nprint 2;\nprint (;\nvar a = 3;").into_iter())
+                .program_with_recovery();
+        assert_eq!(stmts.len(), 2);
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            [
+                "Error at '=': Expect variable name.",
+                "Error at ';': Expect expression."
+            ]
+        );
+    }
+
+    #[test]
+    fn parameter_annotations() {
+        let stmts = parse("fun add(a: int, b) {} var g = fun (c: string) {}; fun h(d) {}")
+            .expect("parsing error");
+        fn annotations(annotations: &Annotations) -> Vec<Option<&str>> {
+            annotations.iter().map(Option::as_deref).collect()
+        }
+        match &stmts[..] {
+            [Stmt::FunctionDecl(f), Stmt::VariableDecl(VariableDecl {
+                definition: Some(Expr::Lambda(_, lambda, _)),
+                ..
+            }), Stmt::FunctionDecl(h)] => {
+                assert_eq!(annotations(&f.annotations), vec![Some("int"), None]);
+                assert_eq!(annotations(lambda), vec![Some("string")]);
+                assert!(h.annotations.is_empty());
+            }
+            stmts => panic!("unexpected program {:?}", stmts),
+        }
+
+        let e = parse("fun f(a:) {}").expect_err("expected a parsing error");
+        assert_eq!(e.to_string(), "Error at ')': Expect parameter type.");
+    }
+
+    #[test]
+    fn bare_return_is_nil() {
+        assert_eq!(
+            parse("return;").expect("parsing error"),
+            vec![Stmt::Return(Expr::Nil, Span::new(1, 1))]
+        );
+    }
+
+    #[test]
+    fn empty_program() {
+        for input in ["", "  \n\t", "// only a comment\n"] {
+            assert_eq!(parse(input).expect("parsing error"), vec![]);
+        }
+    }
+
+    #[test]
+    fn lookahead_two_tokens() {
+        let kinds = |input: &str| -> Vec<&str> {
+            let stmts = parse(input).expect("parsing error");
+            match &stmts[..] {
+                [Stmt::Block(block)] => block.iter().map(Stmt::kind).collect(),
+                stmts => stmts.iter().map(Stmt::kind).collect(),
+            }
+        };
+        assert_eq!(kinds("for (var x in xs) print x;"), ["for"]);
+        assert_eq!(
+            kinds("for (var x = 0; x < 3; x = x + 1) print x;"),
+            ["var", "while"]
+        );
+        assert_eq!(
+            kinds("for (var in = 0; in < 3; in = in + 1) print in;"),
+            ["var", "while"]
+        );
+        assert_eq!(
+            kinds("for (x = 0; x < 3;) print x;"),
+            ["expression", "while"]
+        );
+    }
 }
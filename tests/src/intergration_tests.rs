@@ -1,4 +1,7 @@
 use std::error::Error;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Stdio;
 use std::result::Result;
 use std::{env, process::Command};
 
@@ -11,6 +14,11 @@ macro_rules! regex {
     }};
 }
 
+/// The message of an error report, without the `Error: ` prefix runtime errors get.
+fn report_message(report: &str) -> String {
+    report.strip_prefix("Error: ").unwrap_or(report).to_string()
+}
+
 fn extract_expected_data(_line_num: usize, line: &str) -> Option<String> {
     if let Some(cap) = regex!(r"// expect: ?(.*)").captures_iter(line).next() {
         let capture = &cap[1];
@@ -19,7 +27,7 @@ fn extract_expected_data(_line_num: usize, line: &str) -> Option<String> {
 
     if let Some(cap) = regex!(r"// (Error.*)").captures_iter(line).next() {
         let capture = &cap[1];
-        return Some(format!("{capture}"));
+        return Some(report_message(capture));
     }
 
     if let Some(cap) = regex!(r"// \[((java|c) )?line (\d+)\] (Error.*)")
@@ -30,7 +38,7 @@ fn extract_expected_data(_line_num: usize, line: &str) -> Option<String> {
             return None;
         }
         let capture = &cap[4];
-        return Some(format!("{capture}"));
+        return Some(report_message(capture));
     }
 
     if let Some(cap) = regex!(r"// expect runtime error: (.+)")
@@ -38,7 +46,7 @@ fn extract_expected_data(_line_num: usize, line: &str) -> Option<String> {
         .next()
     {
         let capture = &cap[1];
-        return Some(format!("{capture}"));
+        return Some(report_message(capture));
     }
 
     if let Some(cap) = regex!(r"\[.*line (\d+)\] (Error.+)")
@@ -46,7 +54,7 @@ fn extract_expected_data(_line_num: usize, line: &str) -> Option<String> {
         .next()
     {
         let capture = &cap[2];
-        return Some(format!("{capture}"));
+        return Some(report_message(capture));
     }
 
     if let Some(cap) = regex!(r"(\[line \d+\])").captures_iter(line).next() {
@@ -69,14 +77,49 @@ fn run_test(mut command: Command, source_file: &str, source: &str) -> Result<(),
 
     let output = command.arg(&format!("{source_file}")).output()?;
 
-    let output = String::from_utf8(output.stdout)?;
+    // Program output goes to stdout and the error report, if any, to stderr after it. The
+    // source snippet under a parse error is skipped.
+    let mut actual = String::from_utf8(output.stdout)?;
+    for line in String::from_utf8(output.stderr)?.lines() {
+        if let Some(cap) = regex!(r"^(\[line \d+\] )?(Error.*)").captures(line) {
+            actual.push_str(&format!("{}\n", report_message(&cap[2])));
+        }
+    }
 
-    dbg!(&output, &expected);
-    assert_eq!(output, expected);
+    dbg!(&actual, &expected);
+    assert_eq!(actual, expected);
 
     Ok(())
 }
 
+fn interpreter_binary() -> PathBuf {
+    let mut binary_path =
+        env::current_exe().expect("need current binary path to find binary to test");
+    loop {
+        {
+            let parent = binary_path.parent();
+            if parent.is_none() {
+                panic!(
+                    "Failed to locate binary path from original path: {:?}",
+                    env::current_exe()
+                );
+            }
+            let parent = parent.unwrap();
+            if parent.is_dir() && parent.file_name().unwrap() == "target" {
+                break;
+            }
+        }
+        binary_path.pop();
+    }
+
+    binary_path.push(if cfg!(target_os = "windows") {
+        format!("interpreter_main.exe",)
+    } else {
+        "interpreter_main".into()
+    });
+    binary_path
+}
+
 #[dir_cases(
     "data/assignment",
     "data/block",
@@ -110,33 +153,44 @@ fn run_test(mut command: Command, source_file: &str, source: &str) -> Result<(),
 #[test]
 pub fn crafting_interpreters_test_suite(path: &str, contents: &str) -> Result<(), Box<dyn Error>> {
     dbg!(&path);
-    let mut binary_path =
-        env::current_exe().expect("need current binary path to find binary to test");
-    loop {
-        {
-            let parent = binary_path.parent();
-            if parent.is_none() {
-                panic!(
-                    "Failed to locate binary path from original path: {:?}",
-                    env::current_exe()
-                );
-            }
-            let parent = parent.unwrap();
-            if parent.is_dir() && parent.file_name().unwrap() == "target" {
-                break;
-            }
-        }
-        binary_path.pop();
-    }
-
-    binary_path.push(if cfg!(target_os = "windows") {
-        format!("interpreter_main.exe",)
-    } else {
-        "interpreter_main".into()
-    });
-
+    let binary_path = interpreter_binary();
     dbg!(&binary_path);
     let command = Command::new(binary_path);
 
     run_test(command, &format!("../{}", path), contents)
 }
+
+#[test]
+pub fn run_program_from_stdin() -> Result<(), Box<dyn Error>> {
+    for args in [&["-"][..], &[]] {
+        let mut child = Command::new(interpreter_binary())
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(b"var a = 1;\nprint a + 2;\n")?;
+        let output = child.wait_with_output()?;
+        assert_eq!(String::from_utf8(output.stdout)?, "3\n");
+    }
+    Ok(())
+}
+
+#[test]
+pub fn trace_keeps_program_output_on_stdout() -> Result<(), Box<dyn Error>> {
+    let path = env::temp_dir().join("lox_trace_stdout.lox");
+    std::fs::write(&path, "var a = 1;\nprint a + 2;\n")?;
+    let output = Command::new(interpreter_binary())
+        .arg("--trace")
+        .arg(&path)
+        .output()?;
+    assert_eq!(String::from_utf8(output.stdout)?, "3\n");
+    assert_eq!(
+        String::from_utf8(output.stderr)?,
+        "[line 1] var\n[line 2] print\n"
+    );
+    Ok(())
+}